@@ -28,7 +28,7 @@ fn create_default_config_if_not_exists(shelf_config_dir: &Path) -> Result<(), Bo
     }
 
     let default_config_content = r#"# Shelf configuration file.
-# You can customize the behavior of `shelf prompt` command here.
+# You can customize the behavior of `shelf prompt`, `shelf dots` and `shelf commit` commands here.
 
 [prompt]
 # List of directory names to skip when collecting files.
@@ -49,6 +49,76 @@ skip_files = [
     "*.log",
     ".DS_Store",
 ]
+
+[dots]
+# Files larger than this many bytes trigger a warning when tracked.
+max_file_size = 10485760
+# Refuse to track oversized files instead of only warning.
+refuse_oversized = false
+
+# Locations outside the home directory that may be tracked. Each root maps an
+# absolute prefix to a namespace inside the vault.
+# [[dots.roots]]
+# name = "etc"
+# path = "/etc"
+# sudo = true
+
+[commit]
+# Estimated tokens of staged changes sent to the model. Larger diffs are
+# condensed: lockfiles and generated files shrink to a stat line, long hunks
+# are truncated and the least important files are summarised separately.
+token_budget = 24000
+
+# Learn the commit style (prefixes, casing, tense, length, body, emoji and
+# trailers) from the repository's history and ask the model to follow it.
+# `shelf commit style` shows what was learned.
+learn_style = true
+
+# Language to write messages in, such as "German" or "fr". Conventional Commit
+# types stay in English. Detected from recent history when unset.
+# language = "German"
+
+# Paths kept out of the staged diff and history sent to the model. Patterns
+# without a `/` match file names anywhere, a trailing `/` matches a directory.
+ignore = [
+    "*.lock",
+]
+
+# Per-model overrides of `token_budget`.
+[commit.model_token_budgets]
+# "gemini-2.5-pro" = 200000
+
+# Uncomment to reference ticket IDs found in branch names such as
+# `feat/PROJ-1234-some-thing` with a `Refs: PROJ-1234` trailer.
+# [commit.tickets]
+# patterns = ['\b([A-Z][A-Z0-9]+-[0-9]+)\b']
+# trailer = "Refs"
+
+# Trailers appended to every generated message.
+# [[commit.trailers]]
+# token = "Co-authored-by"
+# value = "Jane Doe <jane@example.com>"
+
+# Rules checked by `shelf commit lint`, which also works as a `commit-msg`
+# hook: `shelf commit lint --file "$1"`.
+[commit.lint]
+max_subject_length = 80
+require_blank_line = true
+max_body_line_length = 80
+forbidden_words = ["WIP"]
+conventional = false
+required_trailers = []
+
+# Uncomment to require Conventional Commits from `shelf commit`.
+# [commit.conventional]
+# types = ["build", "chore", "ci", "docs", "feat", "fix", "perf", "refactor", "revert", "style", "test"]
+# Allowed scopes; any scope is accepted when empty.
+# scopes = []
+# require_scope = false
+# max_header_length = 72
+# max_body_line_length = 72
+# Times to ask the model again when a message can't be repaired.
+# max_regenerations = 2
 "#;
 
     fs::write(&config_file_path, default_config_content)
@@ -0,0 +1,393 @@
+use anyhow::{Context, Result, anyhow};
+use clap::Args;
+use handlebars::Handlebars;
+use rig::{client::builder::DynClientBuilder, completion::Prompt};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::path::PathBuf;
+
+use super::commit::conventional;
+use super::commit::{load_template_with_fallback, response_text, strip_code_fence};
+use crate::git::{commit_time, range_commits, resolve_range};
+use crate::utils::spin_progress;
+
+const CHANGELOG_TEMPLATE_PATH: &str = "assets/prompts/changelog.hbs";
+const CLASSIFY_TEMPLATE_PATH: &str = "assets/prompts/changelog_classify.hbs";
+const SUMMARY_TEMPLATE_PATH: &str = "assets/prompts/changelog_summary.hbs";
+
+const AI_TEMPERATURE: f64 = 0.2;
+/// Classifications rewrite every unstructured commit, so they need room.
+const CLASSIFY_MAX_TOKENS: u64 = 4096;
+const SUMMARY_MAX_TOKENS: u64 = 400;
+
+const PREAMBLE: &str = "You are a release manager writing clear, accurate changelogs for the users of a software project.";
+const CHANGELOG_HEADER: &str = "# Changelog\n\nAll notable changes to this project will be documented in this file.\n\nThe format is based on [Keep a Changelog](https://keepachangelog.com/en/1.1.0/).\n";
+const UNRELEASED: &str = "Unreleased";
+
+#[derive(Args)]
+pub struct ChangelogCMD {
+    /// Commits to describe: `A..B`, or `A` for `A..HEAD`
+    pub range: String,
+    /// Name of the release heading; the release date is added unless it is "Unreleased"
+    #[arg(short, long, default_value = UNRELEASED)]
+    pub release: String,
+    /// Prepend the result to a changelog file instead of printing it
+    #[arg(short, long, value_name = "FILE", num_args = 0..=1, default_missing_value = "CHANGELOG.md")]
+    pub write: Option<PathBuf>,
+    /// Add a prose highlights section written by the model
+    #[arg(long)]
+    pub ai_summary: bool,
+    /// Put commits that don't follow Conventional Commits under "Other" instead of asking the model
+    #[arg(long)]
+    pub no_ai: bool,
+    /// Include housekeeping changes (docs, tests, CI, build, chores)
+    #[arg(long)]
+    pub all: bool,
+    /// Handlebars template to render instead of the Keep a Changelog one
+    #[arg(long, value_name = "FILE")]
+    pub template: Option<PathBuf>,
+    /// AI model provider to use for classification and summaries
+    #[arg(short, long, default_value = "gemini")]
+    pub provider: String,
+    /// Specific model to use for classification and summaries
+    #[arg(short, long, default_value = "gemini-2.5-flash-lite")]
+    pub model: String,
+}
+
+/// Keep a Changelog sections in the order they are listed, followed by housekeeping.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, JsonSchema)]
+enum Section {
+    Added,
+    Changed,
+    Deprecated,
+    Removed,
+    Fixed,
+    Security,
+    Other,
+}
+
+impl Section {
+    fn for_type(kind: &str) -> Self {
+        match kind.to_ascii_lowercase().as_str() {
+            "feat" => Self::Added,
+            "fix" => Self::Fixed,
+            "perf" | "refactor" => Self::Changed,
+            "revert" => Self::Removed,
+            "deprecate" => Self::Deprecated,
+            "security" | "sec" => Self::Security,
+            _ => Self::Other,
+        }
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            Self::Added => "Added",
+            Self::Changed => "Changed",
+            Self::Deprecated => "Deprecated",
+            Self::Removed => "Removed",
+            Self::Fixed => "Fixed",
+            Self::Security => "Security",
+            Self::Other => "Other",
+        }
+    }
+}
+
+/// One line of the changelog.
+#[derive(Debug, Serialize)]
+struct Entry {
+    #[serde(skip)]
+    section: Section,
+    text: String,
+    scope: Option<String>,
+    breaking: bool,
+    hash: String,
+}
+
+/// The model's sections for commits without Conventional Commits headers.
+#[derive(Debug, Deserialize, JsonSchema)]
+struct Classification {
+    entries: Vec<ClassifiedCommit>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct ClassifiedCommit {
+    /// Number of the commit as listed.
+    commit: usize,
+    section: Section,
+    /// One-line changelog entry.
+    summary: String,
+}
+
+pub(super) async fn run(args: ChangelogCMD) -> Result<()> {
+    let range = resolve_range(&args.range)?;
+    let commits = range_commits(range)?;
+    if commits.is_empty() {
+        return Err(anyhow!("No commits in {}", args.range));
+    }
+
+    let (mut entries, unstructured) = group_conventional(&commits);
+    if !unstructured.is_empty() {
+        if args.no_ai {
+            entries.extend(
+                unstructured
+                    .iter()
+                    .map(|(hash, message)| unclassified(hash, message)),
+            );
+        } else {
+            entries.extend(classify(&args, &unstructured).await?);
+        }
+    }
+    if !args.all {
+        entries.retain(|entry| entry.section != Section::Other);
+    }
+
+    let summary = if args.ai_summary && !entries.is_empty() {
+        summarise(&args, &entries).await?
+    } else {
+        String::new()
+    };
+    let date = if args.release == UNRELEASED {
+        String::new()
+    } else {
+        format_date(commit_time(range.tip)?)
+    };
+    let release = render(&args, &entries, &summary, &date)?;
+
+    match &args.write {
+        Some(path) => {
+            let existing = match std::fs::read_to_string(path) {
+                Ok(content) => content,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+                Err(e) => {
+                    return Err(e).with_context(|| format!("Failed to read {}", path.display()));
+                }
+            };
+            std::fs::write(path, prepend_release(&existing, &release))
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            println!("Added release {} to {}", args.release, path.display());
+        }
+        None => print!("{release}"),
+    }
+    Ok(())
+}
+
+/// Turns Conventional Commits into entries and returns the other commits,
+/// as short hash and message, for classification.
+fn group_conventional(commits: &[(git2::Oid, String)]) -> (Vec<Entry>, Vec<(String, String)>) {
+    let mut entries = Vec::new();
+    let mut unstructured = Vec::new();
+    for (oid, message) in commits {
+        let hash = oid.to_string()[..7].to_string();
+        match conventional::parse(message) {
+            Some(parsed) => entries.push(Entry {
+                section: Section::for_type(parsed.kind),
+                text: parsed.subject.trim().to_string(),
+                scope: parsed.scope.map(str::to_string),
+                breaking: parsed.breaking
+                    || parsed
+                        .footers
+                        .iter()
+                        .any(|(token, _)| matches!(*token, "BREAKING CHANGE" | "BREAKING-CHANGE")),
+                hash,
+            }),
+            None => unstructured.push((hash, message.clone())),
+        }
+    }
+    (entries, unstructured)
+}
+
+/// Asks the model to sort unstructured commits into sections.
+async fn classify(args: &ChangelogCMD, unstructured: &[(String, String)]) -> Result<Vec<Entry>> {
+    let template = load_template_with_fallback(CLASSIFY_TEMPLATE_PATH)?;
+    let listed: String = unstructured
+        .iter()
+        .enumerate()
+        .map(|(index, (_, message))| format!("COMMIT {}:\n{}\n\n", index + 1, message.trim_end()))
+        .collect();
+    let data = json!({
+        "CLASSIFICATION_SCHEMA": serde_json::to_string_pretty(&schemars::schema_for!(Classification))?,
+        "COMMITS": listed,
+    });
+    let prompt = Handlebars::new()
+        .render_template(&template, &data)
+        .context("Failed to render classification prompt template")?;
+
+    let response = request(args, prompt, CLASSIFY_MAX_TOKENS).await?;
+    let classification: Classification = serde_json::from_str(strip_code_fence(&response))
+        .context("The model did not return a valid classification")?;
+
+    // Commits the model skipped keep their subject under "Other".
+    let mut entries: Vec<Entry> = unstructured
+        .iter()
+        .map(|(hash, message)| unclassified(hash, message))
+        .collect();
+    for classified in classification.entries {
+        if let Some(entry) = classified
+            .commit
+            .checked_sub(1)
+            .and_then(|index| entries.get_mut(index))
+        {
+            entry.section = classified.section;
+            entry.text = classified.summary.trim().to_string();
+        }
+    }
+    Ok(entries)
+}
+
+/// An entry for a commit left unclassified: its subject under "Other".
+fn unclassified(hash: &str, message: &str) -> Entry {
+    Entry {
+        section: Section::Other,
+        text: message
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_string(),
+        scope: None,
+        breaking: false,
+        hash: hash.to_string(),
+    }
+}
+
+/// Asks the model for a prose paragraph highlighting the release.
+async fn summarise(args: &ChangelogCMD, entries: &[Entry]) -> Result<String> {
+    let template = load_template_with_fallback(SUMMARY_TEMPLATE_PATH)?;
+    let listed: String = entries
+        .iter()
+        .map(|entry| format!("- {}: {}\n", entry.section.title(), entry.text))
+        .collect();
+    let prompt = Handlebars::new()
+        .render_template(&template, &json!({ "ENTRIES": listed }))
+        .context("Failed to render summary prompt template")?;
+    Ok(request(args, prompt, SUMMARY_MAX_TOKENS)
+        .await?
+        .trim()
+        .to_string())
+}
+
+async fn request(args: &ChangelogCMD, prompt: String, max_tokens: u64) -> Result<String> {
+    let client = DynClientBuilder::new();
+    let agent = client
+        .agent(&args.provider, &args.model)?
+        .preamble(PREAMBLE)
+        .temperature(AI_TEMPERATURE)
+        .max_tokens(max_tokens)
+        .build();
+
+    let response =
+        spin_progress(|| async { agent.prompt(prompt).await.map_err(anyhow::Error::from) }).await?;
+    Ok(response_text(response))
+}
+
+/// Renders the release section, with entries grouped by section.
+fn render(args: &ChangelogCMD, entries: &[Entry], summary: &str, date: &str) -> Result<String> {
+    let template = match &args.template {
+        Some(path) => std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read template {}", path.display()))?,
+        None => load_template_with_fallback(CHANGELOG_TEMPLATE_PATH)?,
+    };
+
+    let mut sections: Vec<Section> = entries.iter().map(|entry| entry.section).collect();
+    sections.sort();
+    sections.dedup();
+    let sections: Vec<serde_json::Value> = sections
+        .into_iter()
+        .map(|section| {
+            let entries: Vec<&Entry> = entries
+                .iter()
+                .filter(|entry| entry.section == section)
+                .collect();
+            json!({ "title": section.title(), "entries": entries })
+        })
+        .collect();
+
+    let mut handlebars = Handlebars::new();
+    handlebars.register_escape_fn(handlebars::no_escape);
+    handlebars
+        .render_template(
+            &template,
+            &json!({
+                "RELEASE": args.release,
+                "DATE": date,
+                "SUMMARY": summary,
+                "SECTIONS": sections,
+            }),
+        )
+        .context("Failed to render changelog template")
+}
+
+/// Inserts `release` above the newest release of a Keep a Changelog file,
+/// creating the file's header when it is empty.
+fn prepend_release(existing: &str, release: &str) -> String {
+    let release = format!("{}\n", release.trim_end());
+    if existing.trim().is_empty() {
+        return format!("{CHANGELOG_HEADER}\n{release}");
+    }
+
+    let newest = existing
+        .match_indices("## ")
+        .map(|(index, _)| index)
+        .find(|&index| index == 0 || existing[..index].ends_with('\n'));
+    match newest {
+        Some(index) => format!("{}{release}\n{}", &existing[..index], &existing[index..]),
+        None => format!("{}\n\n{release}", existing.trim_end()),
+    }
+}
+
+/// Formats a commit time as `YYYY-MM-DD` in the committer's time zone.
+fn format_date(time: git2::Time) -> String {
+    let local = time.seconds() + i64::from(time.offset_minutes()) * 60;
+    let (year, month, day) = civil_from_days(local.div_euclid(86_400));
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Converts days since 1970-01-01 to a proleptic Gregorian date.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conventional_commits_group_into_keep_a_changelog_sections() {
+        let commits = vec![
+            (git2::Oid::zero(), "feat(cli)!: add changelog".to_string()),
+            (git2::Oid::zero(), "fix: handle empty ranges".to_string()),
+            (git2::Oid::zero(), "Tweak some things".to_string()),
+            (git2::Oid::zero(), "chore: bump deps".to_string()),
+        ];
+        let (entries, unstructured) = group_conventional(&commits);
+        let sections: Vec<Section> = entries.iter().map(|entry| entry.section).collect();
+        assert_eq!(sections, [Section::Added, Section::Fixed, Section::Other]);
+        assert!(entries[0].breaking);
+        assert_eq!(entries[0].scope.as_deref(), Some("cli"));
+        assert_eq!(unstructured.len(), 1);
+    }
+
+    #[test]
+    fn releases_are_prepended_above_the_newest_one() {
+        let existing = format!("{CHANGELOG_HEADER}\n## [1.0.0] - 2024-01-01\n\n### Added\n\n- a\n");
+        let updated = prepend_release(&existing, "## [1.1.0] - 2024-02-01\n\n### Fixed\n\n- b\n");
+        assert!(updated.starts_with(CHANGELOG_HEADER));
+        let new = updated.find("## [1.1.0]").unwrap();
+        let old = updated.find("## [1.0.0]").unwrap();
+        assert!(new < old);
+
+        assert!(prepend_release("", "## [Unreleased]\n").starts_with("# Changelog"));
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(20_742), (2026, 10, 16));
+    }
+}
@@ -0,0 +1,296 @@
+use std::path::Path;
+
+use crate::git::FileChange;
+
+/// Rough average for code and English; good enough to stay clear of context limits.
+const CHARS_PER_TOKEN: usize = 4;
+/// Hunks longer than this many lines are cut down to `KEPT_HUNK_LINES`.
+const MAX_HUNK_LINES: usize = 80;
+const KEPT_HUNK_LINES: usize = 50;
+
+const LOCKFILE_NAMES: [&str; 9] = [
+    "Cargo.lock",
+    "package-lock.json",
+    "npm-shrinkwrap.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "poetry.lock",
+    "Gemfile.lock",
+    "composer.lock",
+    "go.sum",
+];
+const GENERATED_SUFFIXES: [&str; 7] = [
+    ".min.js", ".min.css", ".map", ".pb.go", "_pb2.py", ".g.dart", ".snap",
+];
+const GENERATED_DIRECTORIES: [&str; 4] = ["vendor", "node_modules", "dist", "third_party"];
+/// Markers tools put near the top of files they generate.
+const GENERATED_MARKERS: [&str; 3] = ["@generated", "DO NOT EDIT", "Code generated by"];
+
+/// How much a file's changes tell about the commit, from least to most.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Importance {
+    Collapsed,
+    Docs,
+    Tests,
+    Config,
+    Source,
+}
+
+/// Staged changes cut down to fit a token budget.
+#[derive(Debug, Default)]
+pub(crate) struct BudgetedChanges {
+    /// Patches that fit the budget, followed by stat lines for collapsed files.
+    pub(crate) diff: String,
+    /// Files that didn't fit, most important first, to be summarised separately.
+    pub(crate) overflow: Vec<FileChange>,
+}
+
+/// Estimates how many tokens `text` costs.
+pub(super) fn estimate_tokens(text: &str) -> usize {
+    text.len().div_ceil(CHARS_PER_TOKEN)
+}
+
+/// Fits `changes` into `budget` tokens.
+///
+/// Binary, lock and generated files collapse to a stat line and long hunks
+/// are truncated. Remaining files are taken by importance while they fit; the rest
+/// are returned as overflow.
+pub(crate) fn fit_to_budget(changes: Vec<FileChange>, budget: usize) -> BudgetedChanges {
+    let mut stats = String::new();
+    let mut candidates = Vec::new();
+    for (position, mut change) in changes.into_iter().enumerate() {
+        if change.binary {
+            stats.push_str(&stat_line(&change, "binary"));
+            continue;
+        }
+        let importance = importance(&change);
+        if importance == Importance::Collapsed {
+            stats.push_str(&stat_line(&change, "collapsed"));
+            continue;
+        }
+        change.patch = truncate_hunks(&change.patch);
+        candidates.push((position, importance, change));
+    }
+
+    // Most important first; among equals, smaller patches first so more files fit.
+    candidates.sort_by_key(|(_, importance, change)| {
+        (std::cmp::Reverse(*importance), change.patch.len())
+    });
+
+    let mut remaining = budget.saturating_sub(estimate_tokens(&stats));
+    let mut included = Vec::new();
+    let mut overflow = Vec::new();
+    for (position, _, change) in candidates {
+        let cost = estimate_tokens(&change.patch);
+        if cost <= remaining {
+            remaining -= cost;
+            included.push((position, change));
+        } else {
+            overflow.push(change);
+        }
+    }
+
+    // Keep the patch in the order git produced it.
+    included.sort_by_key(|(position, _)| *position);
+    let mut diff: String = included
+        .into_iter()
+        .map(|(_, change)| change.patch)
+        .collect();
+    if !stats.is_empty() {
+        diff.push_str("\nCollapsed files:\n");
+        diff.push_str(&stats);
+    }
+
+    BudgetedChanges { diff, overflow }
+}
+
+/// One-line summary of a file's changes.
+pub(crate) fn stat_line(change: &FileChange, note: &str) -> String {
+    format!(
+        " {} | +{} -{} ({note})\n",
+        change.path.display(),
+        change.insertions,
+        change.deletions
+    )
+}
+
+/// Cuts `text` down to roughly `budget` tokens, marking the cut.
+pub(super) fn truncate_to_tokens(text: &str, budget: usize) -> String {
+    let limit = budget * CHARS_PER_TOKEN;
+    if text.len() <= limit {
+        return text.to_string();
+    }
+    let mut end = limit;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!(
+        "{}\n[... truncated to fit the token budget ...]\n",
+        &text[..end]
+    )
+}
+
+fn importance(change: &FileChange) -> Importance {
+    let path = &change.path;
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    let in_directory = |directories: &[&str]| {
+        path.components()
+            .any(|component| directories.iter().any(|dir| component.as_os_str() == *dir))
+    };
+
+    if LOCKFILE_NAMES.contains(&name)
+        || name.ends_with(".lock")
+        || GENERATED_SUFFIXES
+            .iter()
+            .any(|suffix| name.ends_with(suffix))
+        || in_directory(&GENERATED_DIRECTORIES)
+        || is_marked_generated(&change.patch)
+    {
+        return Importance::Collapsed;
+    }
+
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default();
+    if in_directory(&["tests", "test", "__tests__", "spec"])
+        || name.contains("_test.")
+        || name.contains(".test.")
+        || name.contains(".spec.")
+    {
+        Importance::Tests
+    } else if matches!(extension, "md" | "rst" | "txt" | "adoc") || in_directory(&["docs"]) {
+        Importance::Docs
+    } else if matches!(
+        extension,
+        "toml" | "yaml" | "yml" | "json" | "ini" | "cfg" | "conf" | "xml"
+    ) || is_dotfile(path)
+    {
+        Importance::Config
+    } else {
+        Importance::Source
+    }
+}
+
+fn is_dotfile(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('.'))
+}
+
+/// Looks for a generator marker in the first added lines of the patch.
+fn is_marked_generated(patch: &str) -> bool {
+    patch
+        .lines()
+        .filter(|line| line.starts_with('+') && !line.starts_with("+++"))
+        .take(5)
+        .any(|line| GENERATED_MARKERS.iter().any(|marker| line.contains(marker)))
+}
+
+/// Shortens every hunk longer than `MAX_HUNK_LINES`, noting how much was cut.
+fn truncate_hunks(patch: &str) -> String {
+    let mut output = String::new();
+    let mut hunk: Vec<&str> = Vec::new();
+
+    let flush = |hunk: &mut Vec<&str>, output: &mut String| {
+        let keep = if hunk.len() > MAX_HUNK_LINES {
+            KEPT_HUNK_LINES
+        } else {
+            hunk.len()
+        };
+        for line in &hunk[..keep] {
+            output.push_str(line);
+            output.push('\n');
+        }
+        if keep < hunk.len() {
+            output.push_str(&format!(
+                "[... {} more lines in this hunk ...]\n",
+                hunk.len() - keep
+            ));
+        }
+        hunk.clear();
+    };
+
+    let mut in_hunk = false;
+    for line in patch.lines() {
+        if line.starts_with("@@") || line.starts_with("diff --git") {
+            flush(&mut hunk, &mut output);
+            in_hunk = line.starts_with("@@");
+            output.push_str(line);
+            output.push('\n');
+        } else if in_hunk {
+            hunk.push(line);
+        } else {
+            output.push_str(line);
+            output.push('\n');
+        }
+    }
+    flush(&mut hunk, &mut output);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn change(path: &str, patch: String) -> FileChange {
+        FileChange {
+            path: PathBuf::from(path),
+            insertions: patch.lines().filter(|line| line.starts_with('+')).count(),
+            deletions: 0,
+            patch,
+            binary: false,
+        }
+    }
+
+    fn patch_for(path: &str, added_lines: usize) -> String {
+        let mut patch = format!("diff --git a/{path} b/{path}\n@@ -0,0 +1,{added_lines} @@\n");
+        for n in 0..added_lines {
+            patch.push_str(&format!("+line {n}\n"));
+        }
+        patch
+    }
+
+    #[test]
+    fn lockfiles_collapse_and_long_hunks_are_truncated() {
+        let budgeted = fit_to_budget(
+            vec![
+                change("Cargo.lock", patch_for("Cargo.lock", 500)),
+                change("src/main.rs", patch_for("src/main.rs", 200)),
+            ],
+            10_000,
+        );
+        assert!(budgeted.overflow.is_empty());
+        assert!(budgeted.diff.contains(" Cargo.lock | +500 -0 (collapsed)"));
+        assert!(!budgeted.diff.contains("diff --git a/Cargo.lock"));
+        assert!(
+            budgeted
+                .diff
+                .contains("+line 49\n[... 150 more lines in this hunk ...]")
+        );
+        assert!(!budgeted.diff.contains("+line 50\n"));
+
+        let logo = FileChange {
+            binary: true,
+            ..change("assets/logo.png", String::new())
+        };
+        let budgeted = fit_to_budget(vec![logo], 10_000);
+        assert!(budgeted.diff.contains(" assets/logo.png | +0 -0 (binary)"));
+    }
+
+    #[test]
+    fn least_important_files_overflow_first() {
+        let source = change("src/lib.rs", patch_for("src/lib.rs", 40));
+        let docs = change("README.md", patch_for("README.md", 40));
+        let budget = estimate_tokens(&source.patch) + 10;
+
+        let budgeted = fit_to_budget(vec![docs, source], budget);
+        assert!(budgeted.diff.contains("src/lib.rs"));
+        assert_eq!(budgeted.overflow.len(), 1);
+        assert_eq!(budgeted.overflow[0].path, PathBuf::from("README.md"));
+    }
+}
@@ -0,0 +1,175 @@
+use anyhow::Result;
+use futures::future::try_join_all;
+
+use super::conventional::Violation;
+use super::{AI_TEMPERATURE, CommitConfig, generate_valid_commit_message};
+
+/// How much hotter each further candidate in a batch is sampled.
+const TEMPERATURE_STEP: f64 = 0.3;
+const MAX_TEMPERATURE: f64 = 1.0;
+
+/// Where a suggestion came from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum Origin {
+    Generated { temperature: f64 },
+    Merged,
+}
+
+/// One suggestion seen during the session.
+#[derive(Debug)]
+pub(super) struct Candidate {
+    pub(super) message: String,
+    pub(super) violations: Vec<Violation>,
+    pub(super) origin: Origin,
+}
+
+/// Every suggestion seen in the session, in order, and which one is on screen.
+#[derive(Debug, Default)]
+pub(super) struct CandidateHistory {
+    candidates: Vec<Candidate>,
+    current: usize,
+}
+
+impl CandidateHistory {
+    /// Adds a batch of suggestions and shows the first of them.
+    pub(super) fn extend(&mut self, batch: Vec<Candidate>) {
+        if !batch.is_empty() {
+            self.current = self.candidates.len();
+            self.candidates.extend(batch);
+        }
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.candidates.is_empty()
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.candidates.len()
+    }
+
+    pub(super) fn current_index(&self) -> usize {
+        self.current
+    }
+
+    pub(super) fn current(&self) -> &Candidate {
+        &self.candidates[self.current]
+    }
+
+    pub(super) fn current_mut(&mut self) -> &mut Candidate {
+        &mut self.candidates[self.current]
+    }
+
+    pub(super) fn get(&self, index: usize) -> Option<&Candidate> {
+        self.candidates.get(index)
+    }
+
+    pub(super) fn select(&mut self, index: usize) {
+        if index < self.candidates.len() {
+            self.current = index;
+        }
+    }
+
+    /// One line per suggestion for a selection list.
+    pub(super) fn labels(&self) -> Vec<String> {
+        self.candidates
+            .iter()
+            .enumerate()
+            .map(|(index, candidate)| {
+                let subject = candidate.message.lines().next().unwrap_or_default();
+                let origin = match candidate.origin {
+                    Origin::Generated { temperature } => format!("t={temperature:.1}"),
+                    Origin::Merged => "merged".to_string(),
+                };
+                let flag = if candidate.violations.is_empty() {
+                    ""
+                } else {
+                    " ✗"
+                };
+                format!("{:>2}. {subject} ({origin}){flag}", index + 1)
+            })
+            .collect()
+    }
+}
+
+/// Temperatures for a batch of `count` candidates, rising from the default.
+pub(super) fn temperatures(count: usize) -> Vec<f64> {
+    (0..count)
+        .map(|index| (AI_TEMPERATURE + TEMPERATURE_STEP * index as f64).min(MAX_TEMPERATURE))
+        .collect()
+}
+
+/// Requests `count` suggestions concurrently, each at its own temperature.
+pub(super) async fn generate(config: &CommitConfig<'_>, count: usize) -> Result<Vec<Candidate>> {
+    let temperatures = temperatures(count.max(1));
+    let configs: Vec<CommitConfig<'_>> = temperatures
+        .iter()
+        .map(|&temperature| CommitConfig {
+            temperature,
+            ..*config
+        })
+        .collect();
+    let messages = try_join_all(configs.iter().map(generate_valid_commit_message)).await?;
+
+    Ok(messages
+        .into_iter()
+        .zip(temperatures)
+        .map(|((message, violations), temperature)| Candidate {
+            message,
+            violations,
+            origin: Origin::Generated { temperature },
+        })
+        .collect())
+}
+
+/// Editor content for merging two suggestions; the comment lines are dropped on save.
+pub(super) fn merge_draft(first: &str, second: &str, comment: char) -> String {
+    format!(
+        "{comment} Merge the two suggestions below into one message.\n\
+         {comment} Lines starting with '{comment}' are ignored.\n\
+         {comment} ---- first suggestion ----\n\
+         {}\n\
+         {comment} ---- second suggestion ----\n\
+         {}\n",
+        first.trim_end(),
+        second.trim_end()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::commit::split_comment_lines;
+
+    fn candidate(message: &str) -> Candidate {
+        Candidate {
+            message: message.to_string(),
+            violations: Vec::new(),
+            origin: Origin::Generated { temperature: 0.2 },
+        }
+    }
+
+    #[test]
+    fn temperatures_rise_and_are_capped() {
+        assert_eq!(temperatures(1), vec![AI_TEMPERATURE]);
+        let spread = temperatures(5);
+        assert!(spread.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(spread.last(), Some(&MAX_TEMPERATURE));
+    }
+
+    #[test]
+    fn history_keeps_earlier_batches_and_merges_strip_comments() {
+        let mut history = CandidateHistory::default();
+        history.extend(vec![candidate("feat: a"), candidate("feat: b")]);
+        history.extend(vec![candidate("feat: c")]);
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.current().message, "feat: c");
+
+        history.select(1);
+        assert_eq!(history.current().message, "feat: b");
+        assert!(history.labels()[0].starts_with(" 1. feat: a (t=0.2)"));
+
+        let draft = merge_draft("feat: a\n\nBody a.", "feat: b", '#');
+        let (text, _) = split_comment_lines(&draft, '#');
+        assert_eq!(text, "feat: a\n\nBody a.\nfeat: b");
+    }
+}
@@ -0,0 +1,342 @@
+use thiserror::Error;
+
+use crate::config::ConventionalConfig;
+
+/// Footer token that marks a breaking change, as defined by Conventional Commits.
+const BREAKING_CHANGE_TOKEN: &str = "BREAKING CHANGE";
+
+/// A commit message split into its Conventional Commits parts.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct ConventionalMessage<'a> {
+    pub(crate) kind: &'a str,
+    pub(crate) scope: Option<&'a str>,
+    pub(crate) breaking: bool,
+    pub(crate) subject: &'a str,
+    pub(crate) body: Vec<&'a str>,
+    pub(crate) footers: Vec<(&'a str, &'a str)>,
+}
+
+/// A rule a commit message breaks.
+#[derive(Debug, PartialEq, Eq, Error)]
+pub(super) enum Violation {
+    #[error("header is not in the form `type(scope)!: subject`")]
+    MalformedHeader,
+    #[error("type `{0}` is not one of the allowed types")]
+    UnknownType(String),
+    #[error("scope `{0}` is not one of the allowed scopes")]
+    UnknownScope(String),
+    #[error("a scope is required")]
+    MissingScope,
+    #[error("subject is empty")]
+    EmptySubject,
+    #[error("header is {0} characters long, the limit is {1}")]
+    HeaderTooLong(usize, usize),
+    #[error("header must be followed by a blank line")]
+    MissingBlankLine,
+    #[error("body line {0} is {1} characters long, wrap at {2}")]
+    BodyLineTooLong(usize, usize, usize),
+}
+
+/// Parses `message` into its header parts, body lines and trailing footers.
+///
+/// Returns `None` when the header isn't a Conventional Commits header.
+pub(crate) fn parse(message: &str) -> Option<ConventionalMessage<'_>> {
+    let mut lines = message.lines();
+    let (prefix, subject) = lines.next()?.split_once(':')?;
+
+    let (prefix, breaking) = match prefix.strip_suffix('!') {
+        Some(prefix) => (prefix, true),
+        None => (prefix, false),
+    };
+    let (kind, scope) = match prefix.split_once('(') {
+        Some((kind, scope)) => (kind, Some(scope.strip_suffix(')')?)),
+        None => (prefix, None),
+    };
+    if kind.is_empty() || !kind.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return None;
+    }
+
+    let rest: Vec<&str> = lines.collect();
+    let last_paragraph = rest
+        .iter()
+        .rposition(|line| line.trim().is_empty())
+        .map_or(0, |blank| blank + 1);
+    let footers: Option<Vec<_>> = rest[last_paragraph..]
+        .iter()
+        .map(|line| parse_footer(line))
+        .collect();
+
+    let (body, footers) = match footers {
+        // A lone paragraph directly under the header is body, not footers.
+        Some(footers) if !footers.is_empty() && last_paragraph > 0 => {
+            (&rest[..last_paragraph], footers)
+        }
+        _ => (&rest[..], Vec::new()),
+    };
+
+    Some(ConventionalMessage {
+        kind,
+        scope,
+        breaking,
+        subject: subject.trim(),
+        body: body.to_vec(),
+        footers,
+    })
+}
+
+/// Parses a `Token: value` or `Token #value` footer line.
+fn parse_footer(line: &str) -> Option<(&str, &str)> {
+    let (token, value) = line.split_once(": ").or_else(|| line.split_once(" #"))?;
+    let valid_token = token == BREAKING_CHANGE_TOKEN
+        || token == "BREAKING-CHANGE"
+        || (!token.is_empty() && token.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'));
+    valid_token.then_some((token, value))
+}
+
+/// Checks `message` against `rules`, returning every violation found.
+pub(super) fn validate(message: &str, rules: &ConventionalConfig) -> Vec<Violation> {
+    let Some(parsed) = parse(message) else {
+        return vec![Violation::MalformedHeader];
+    };
+    let mut violations = Vec::new();
+
+    if !rules.types.iter().any(|kind| kind == parsed.kind) {
+        violations.push(Violation::UnknownType(parsed.kind.to_string()));
+    }
+    match parsed.scope {
+        Some(scope) if !rules.scopes.is_empty() && !rules.scopes.iter().any(|s| s == scope) => {
+            violations.push(Violation::UnknownScope(scope.to_string()));
+        }
+        None if rules.require_scope => violations.push(Violation::MissingScope),
+        _ => {}
+    }
+    if parsed.subject.is_empty() {
+        violations.push(Violation::EmptySubject);
+    }
+
+    let header_length = message
+        .lines()
+        .next()
+        .map_or(0, |line| line.chars().count());
+    if header_length > rules.max_header_length {
+        violations.push(Violation::HeaderTooLong(
+            header_length,
+            rules.max_header_length,
+        ));
+    }
+
+    if parsed
+        .body
+        .first()
+        .is_some_and(|line| !line.trim().is_empty())
+    {
+        violations.push(Violation::MissingBlankLine);
+    }
+    for (index, line) in parsed.body.iter().enumerate() {
+        let length = line.chars().count();
+        // Unbreakable lines such as URLs can't be wrapped.
+        if length > rules.max_body_line_length && line.trim().contains(' ') {
+            violations.push(Violation::BodyLineTooLong(
+                index + 2,
+                length,
+                rules.max_body_line_length,
+            ));
+        }
+    }
+
+    violations
+}
+
+/// Fixes what can be fixed without asking the model again: stray code fences
+/// and labels, type casing, a trailing period on the subject, a missing blank
+/// line under the header and unwrapped body paragraphs.
+pub(super) fn repair(message: &str, rules: &ConventionalConfig) -> String {
+    let message = strip_wrapping(message);
+    let mut lines = message.lines();
+    let header = repair_header(lines.next().unwrap_or_default(), rules);
+
+    let rest: Vec<&str> = lines.collect();
+    let footer_start = parse(&message)
+        .filter(|parsed| !parsed.footers.is_empty())
+        .map_or(rest.len(), |parsed| rest.len() - parsed.footers.len());
+
+    let mut repaired = vec![header];
+    if rest.first().is_some_and(|line| !line.trim().is_empty()) {
+        repaired.push(String::new());
+    }
+    for line in &rest[..footer_start] {
+        repaired.extend(wrap_line(line, rules.max_body_line_length));
+    }
+    repaired.extend(rest[footer_start..].iter().map(|line| line.to_string()));
+
+    repaired.join("\n").trim_end().to_string()
+}
+
+/// Removes Markdown code fences and the completion label the prompt examples use.
+fn strip_wrapping(message: &str) -> String {
+    let trimmed = message.trim();
+    let trimmed = trimmed
+        .strip_prefix("COMPLETED_COMMIT_MESSAGE:")
+        .unwrap_or(trimmed)
+        .trim();
+    match trimmed.strip_prefix("```") {
+        Some(fenced) => {
+            // Drop the info string on the opening fence, if any.
+            let body = fenced.split_once('\n').map_or("", |(_, body)| body);
+            body.trim_end().trim_end_matches("```").trim().to_string()
+        }
+        None => trimmed.to_string(),
+    }
+}
+
+fn repair_header(header: &str, rules: &ConventionalConfig) -> String {
+    let header = header.trim();
+    let Some((prefix, subject)) = header.split_once(':') else {
+        return header.to_string();
+    };
+
+    let lowered = prefix.to_ascii_lowercase();
+    let kind_end = lowered.find(['(', '!']).unwrap_or(lowered.len());
+    let prefix = if rules.types.iter().any(|kind| *kind == lowered[..kind_end]) {
+        format!("{}{}", &lowered[..kind_end], &prefix[kind_end..])
+    } else {
+        prefix.to_string()
+    };
+
+    let subject = subject.trim();
+    let subject = subject.strip_suffix('.').unwrap_or(subject);
+    format!("{prefix}: {subject}")
+}
+
+/// Wraps a body line at `width`, keeping list bullets on a hanging indent.
+fn wrap_line(line: &str, width: usize) -> Vec<String> {
+    if line.chars().count() <= width || !line.trim().contains(' ') {
+        return vec![line.to_string()];
+    }
+
+    let indent_width = line.len() - line.trim_start().len();
+    let bullet_width = ["- ", "* "]
+        .iter()
+        .find(|bullet| line.trim_start().starts_with(*bullet))
+        .map_or(0, |bullet| bullet.len());
+    let hanging = " ".repeat(indent_width + bullet_width);
+
+    let mut wrapped = Vec::new();
+    let mut current = line[..indent_width].to_string();
+    for word in line.split_whitespace() {
+        let at_line_start = current.trim().is_empty();
+        if !at_line_start && current.chars().count() + 1 + word.chars().count() > width {
+            wrapped.push(std::mem::replace(&mut current, hanging.clone()));
+        }
+        if !current.trim().is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    wrapped.push(current);
+    wrapped
+}
+
+/// Describes the rules for the prompt, followed by any violations of a previous attempt.
+pub(super) fn prompt_rules(rules: &ConventionalConfig, violations: &[Violation]) -> String {
+    let mut text = format!(
+        "The message MUST follow Conventional Commits: `type(scope)!: subject`, a blank \
+         line, an optional body wrapped at {} characters, then optional `Token: value` footers.\n\
+         Allowed types: {}.\n",
+        rules.max_body_line_length,
+        rules.types.join(", ")
+    );
+    match (rules.scopes.is_empty(), rules.require_scope) {
+        (true, true) => text.push_str("A scope is required.\n"),
+        (true, false) => {}
+        (false, required) => text.push_str(&format!(
+            "{} scopes: {}.\n",
+            if required { "Required" } else { "Allowed" },
+            rules.scopes.join(", ")
+        )),
+    }
+    text.push_str(&format!(
+        "The header must not exceed {} characters.\n",
+        rules.max_header_length
+    ));
+
+    if !violations.is_empty() {
+        text.push_str("\nThe previous suggestion was rejected because:\n");
+        for violation in violations {
+            text.push_str(&format!("- {violation}\n"));
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules() -> ConventionalConfig {
+        ConventionalConfig {
+            scopes: vec!["cli".to_string(), "dots".to_string()],
+            ..ConventionalConfig::default()
+        }
+    }
+
+    #[test]
+    fn parses_header_body_and_footers() {
+        let message =
+            "feat(cli)!: add --print\n\nPrints the message.\n\nRefs #12\nBREAKING CHANGE: drops -p";
+        let parsed = parse(message).expect("conventional header");
+        assert_eq!(parsed.kind, "feat");
+        assert_eq!(parsed.scope, Some("cli"));
+        assert!(parsed.breaking);
+        assert_eq!(parsed.subject, "add --print");
+        assert_eq!(parsed.body, vec!["", "Prints the message.", ""]);
+        assert_eq!(
+            parsed.footers,
+            vec![("Refs", "12"), (BREAKING_CHANGE_TOKEN, "drops -p")]
+        );
+
+        assert!(parse("Add --print").is_none());
+        assert!(parse("feat(cli: add --print").is_none());
+    }
+
+    #[test]
+    fn validate_reports_every_violation() {
+        let long_line = "word ".repeat(20);
+        let message = format!("Feature(core): \n{long_line}");
+        assert_eq!(
+            validate(&message, &rules()),
+            vec![
+                Violation::UnknownType("Feature".to_string()),
+                Violation::UnknownScope("core".to_string()),
+                Violation::EmptySubject,
+                Violation::MissingBlankLine,
+                Violation::BodyLineTooLong(2, long_line.len(), 72),
+            ]
+        );
+        assert!(validate("fix(dots): handle missing vault", &rules()).is_empty());
+        assert_eq!(
+            validate(
+                "fix: handle it",
+                &ConventionalConfig {
+                    require_scope: true,
+                    ..rules()
+                }
+            ),
+            vec![Violation::MissingScope]
+        );
+    }
+
+    #[test]
+    fn repair_fixes_formatting_without_touching_footers() {
+        let long_line = "word ".repeat(20);
+        let message = format!(
+            "```text\nFix(dots): handle missing vault.\n{}\n\nRefs: {}\n```",
+            long_line.trim(),
+            long_line.trim()
+        );
+        let repaired = repair(&message, &rules());
+        assert!(validate(&repaired, &rules()).is_empty(), "{repaired}");
+        assert!(repaired.starts_with("fix(dots): handle missing vault\n\nword word"));
+        assert!(repaired.ends_with(&format!("Refs: {}", long_line.trim())));
+    }
+}
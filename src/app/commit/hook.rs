@@ -0,0 +1,230 @@
+use anyhow::{Context, Result, anyhow};
+use clap::Subcommand;
+use colored::Colorize;
+use git2::Repository;
+use std::fs;
+use std::path::Path;
+
+use super::{
+    CommitConfig, comment_char, generate_valid_commit_message, split_comment_lines, trailers,
+};
+use crate::git::hooks_dir;
+use crate::utils::shine_success;
+
+const HOOK_NAME: &str = "prepare-commit-msg";
+/// Marker line identifying hooks written by shelf, so foreign hooks are never touched.
+const HOOK_MARKER: &str = "# Installed by shelf";
+
+/// `prepare-commit-msg` sources for which git already has a message to use.
+const PROVIDED_SOURCES: [&str; 4] = ["message", "merge", "squash", "commit"];
+
+#[derive(Subcommand)]
+pub enum HookAction {
+    /// Install the `prepare-commit-msg` hook in the current repository.
+    Install {
+        /// Replace an existing hook that was not installed by shelf.
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Remove the hook installed by shelf.
+    Uninstall,
+    /// Show whether the hook is installed.
+    Status,
+}
+
+pub(super) fn run(action: &HookAction) -> Result<()> {
+    let repo = Repository::open(".").context("Failed to open local git repository")?;
+    let hook_path = hooks_dir(&repo)?.join(HOOK_NAME);
+
+    match action {
+        HookAction::Install { force } => install(&hook_path, *force),
+        HookAction::Uninstall => uninstall(&hook_path),
+        HookAction::Status => {
+            match hook_state(&hook_path) {
+                HookState::Missing => println!("{}", "Hook not installed".bright_blue()),
+                HookState::Shelf => println!(
+                    "Hook installed at {}",
+                    hook_path.display().to_string().bright_green()
+                ),
+                HookState::Foreign => println!(
+                    "A {HOOK_NAME} hook not managed by shelf exists at {}",
+                    hook_path.display().to_string().bright_yellow()
+                ),
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Fills the message file git hands to `prepare-commit-msg` from the staged diff.
+///
+/// Merges, squashes, amends and messages given with `-m`/`-F` are left alone, as
+/// are message files that already contain text. A commit template is treated as
+/// the partial message to complete.
+pub(super) async fn fill_message_file(
+    config: &CommitConfig<'_>,
+    hook_args: &[String],
+) -> Result<()> {
+    let (message_file, source) = match hook_args {
+        [file, rest @ ..] => (Path::new(file), rest.first().map(String::as_str)),
+        [] => return Err(anyhow!("--hook requires the message file path")),
+    };
+    if source.is_some_and(|source| PROVIDED_SOURCES.contains(&source)) {
+        return Ok(());
+    }
+
+    let existing = fs::read_to_string(message_file)
+        .with_context(|| format!("Failed to read {}", message_file.display()))?;
+    let comment = comment_char();
+    let (text, mut comments) = split_comment_lines(&existing, comment);
+
+    let prefix = if source == Some("template") {
+        text.trim().to_string()
+    } else if !text.trim().is_empty() {
+        return Ok(());
+    } else {
+        String::new()
+    };
+
+    let config = CommitConfig {
+        prefix: Some(&prefix),
+        ..*config
+    };
+    let (message, violations) = generate_valid_commit_message(&config).await?;
+    // Surface remaining rule violations in the editor, where git strips them again.
+    if !violations.is_empty() {
+        let listed: String = violations
+            .iter()
+            .map(|violation| format!("{comment}   - {violation}\n"))
+            .collect();
+        comments = format!("{comment} Commit rule violations:\n{listed}{comment}\n{comments}");
+    }
+
+    let message = trailers::append(&message, config.trailers);
+    let mut content = format!("{}\n", message.trim_end());
+    if !comments.is_empty() {
+        content.push('\n');
+        content.push_str(&comments);
+    }
+    fs::write(message_file, content)
+        .with_context(|| format!("Failed to write {}", message_file.display()))
+}
+
+enum HookState {
+    Missing,
+    Shelf,
+    Foreign,
+}
+
+fn hook_state(hook_path: &Path) -> HookState {
+    match fs::read_to_string(hook_path) {
+        Ok(content) if content.contains(HOOK_MARKER) => HookState::Shelf,
+        Ok(_) => HookState::Foreign,
+        Err(_) if hook_path.exists() => HookState::Foreign,
+        Err(_) => HookState::Missing,
+    }
+}
+
+fn install(hook_path: &Path, force: bool) -> Result<()> {
+    if matches!(hook_state(hook_path), HookState::Foreign) && !force {
+        return Err(anyhow!(
+            "A {HOOK_NAME} hook already exists at {}, use --force to replace it",
+            hook_path.display()
+        ));
+    }
+
+    if let Some(dir) = hook_path.parent() {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create hooks directory {}", dir.display()))?;
+    }
+    fs::write(hook_path, hook_script()?)
+        .with_context(|| format!("Failed to write {}", hook_path.display()))?;
+    make_executable(hook_path)?;
+
+    shine_success(&format!(
+        "Installed {HOOK_NAME} hook at {}",
+        hook_path.display()
+    ));
+    Ok(())
+}
+
+fn uninstall(hook_path: &Path) -> Result<()> {
+    match hook_state(hook_path) {
+        HookState::Missing => {
+            println!("{}", "Hook not installed".bright_blue());
+            Ok(())
+        }
+        HookState::Foreign => Err(anyhow!(
+            "The {HOOK_NAME} hook at {} was not installed by shelf, leaving it in place",
+            hook_path.display()
+        )),
+        HookState::Shelf => {
+            fs::remove_file(hook_path)
+                .with_context(|| format!("Failed to remove {}", hook_path.display()))?;
+            shine_success(&format!("Removed {HOOK_NAME} hook"));
+            Ok(())
+        }
+    }
+}
+
+/// Renders the hook script. A failing generation never blocks `git commit`.
+fn hook_script() -> Result<String> {
+    let shelf = std::env::current_exe().context("Failed to locate the shelf executable")?;
+    Ok(format!(
+        "#!/bin/sh\n\
+         {HOOK_MARKER}; remove with `shelf commit hook uninstall`.\n\
+         SHELF=\"${{SHELF_BIN:-{}}}\"\n\
+         \"$SHELF\" commit --hook \"$@\" || true\n",
+        shelf.display()
+    ))
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+        .with_context(|| format!("Failed to make {} executable", path.display()))
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn install_refuses_foreign_hooks_and_uninstall_keeps_them() -> Result<()> {
+        let dir = tempdir()?;
+        let hook_path = dir.path().join("hooks").join(HOOK_NAME);
+
+        install(&hook_path, false)?;
+        assert!(matches!(hook_state(&hook_path), HookState::Shelf));
+        install(&hook_path, false)?;
+        uninstall(&hook_path)?;
+        assert!(matches!(hook_state(&hook_path), HookState::Missing));
+
+        fs::write(&hook_path, "#!/bin/sh\nexit 0\n")?;
+        assert!(install(&hook_path, false).is_err());
+        assert!(uninstall(&hook_path).is_err());
+        assert!(hook_path.exists());
+        install(&hook_path, true)?;
+        assert!(matches!(hook_state(&hook_path), HookState::Shelf));
+
+        Ok(())
+    }
+
+    #[test]
+    fn message_file_comments_are_kept_apart() {
+        let content = "feat: \n\n# Please enter the commit message\n# On branch main\n";
+        let (text, comments) = split_comment_lines(content, '#');
+        assert_eq!(text, "feat:");
+        assert_eq!(
+            comments,
+            "# Please enter the commit message\n# On branch main\n"
+        );
+    }
+}
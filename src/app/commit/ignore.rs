@@ -0,0 +1,120 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use glob::{MatchOptions, Pattern};
+use std::path::Path;
+
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// Glob patterns for paths kept out of the commit context, from `[commit] ignore`
+/// and `--ignored`.
+///
+/// A pattern without a `/` matches file names anywhere (`*.lock`), one with a
+/// `/` matches the path from the repository root (`docs/**/*.svg`), and a
+/// trailing `/` matches everything under a directory (`vendor/`).
+pub(crate) struct IgnoreFilter {
+    rules: Vec<IgnoreRule>,
+}
+
+struct IgnoreRule {
+    pattern: String,
+    source: &'static str,
+    glob: Pattern,
+}
+
+impl IgnoreFilter {
+    pub(crate) fn new(configured: &[String], from_args: &[String]) -> Result<Self> {
+        let configured = configured.iter().map(|pattern| (pattern, "shelf.toml"));
+        let from_args = from_args.iter().map(|pattern| (pattern, "--ignored"));
+        let rules = configured
+            .chain(from_args)
+            .filter(|(pattern, _)| !pattern.trim().is_empty())
+            .map(|(pattern, source)| {
+                let glob = Pattern::new(pattern.trim().trim_end_matches('/'))
+                    .with_context(|| format!("Invalid ignore pattern {pattern:?}"))?;
+                Ok(IgnoreRule {
+                    pattern: pattern.trim().to_string(),
+                    source,
+                    glob,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { rules })
+    }
+
+    /// Returns `true` if any pattern matches `path`, relative to the repository root.
+    pub(crate) fn is_ignored(&self, path: &Path) -> bool {
+        self.rules.iter().any(|rule| rule.matches(path))
+    }
+
+    /// Prints the effective patterns and where each one comes from.
+    pub(super) fn explain(&self) {
+        println!("{}", "Ignore patterns:".bold());
+        if self.rules.is_empty() {
+            println!("  {}", "(none)".dimmed());
+        }
+        for rule in &self.rules {
+            println!(
+                "  {:<30} {}",
+                rule.pattern,
+                format!("from {}", rule.source).dimmed()
+            );
+        }
+    }
+}
+
+impl IgnoreRule {
+    fn matches(&self, path: &Path) -> bool {
+        let directory_only = self.pattern.ends_with('/');
+        let by_name = !self.pattern.trim_end_matches('/').contains('/');
+
+        // Directory patterns match any ancestor; file patterns the path itself.
+        let mut candidates: Vec<&Path> = path
+            .ancestors()
+            .filter(|p| !p.as_os_str().is_empty())
+            .collect();
+        if directory_only {
+            candidates.remove(0);
+        }
+
+        candidates.into_iter().any(|candidate| {
+            if by_name {
+                candidate
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| self.glob.matches_with(name, MATCH_OPTIONS))
+            } else {
+                self.glob.matches_path_with(candidate, MATCH_OPTIONS)
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns_match_names_paths_and_directories() -> Result<()> {
+        let filter = IgnoreFilter::new(
+            &["*.lock".to_string(), "vendor/".to_string()],
+            &["docs/**/*.svg".to_string()],
+        )?;
+
+        assert!(filter.is_ignored(Path::new("Cargo.lock")));
+        assert!(filter.is_ignored(Path::new("web/yarn.lock")));
+        assert!(filter.is_ignored(Path::new("vendor/lib/a.c")));
+        assert!(filter.is_ignored(Path::new("third/vendor/a.c")));
+        assert!(filter.is_ignored(Path::new("docs/img/arch/diagram.svg")));
+
+        assert!(!filter.is_ignored(Path::new("src/lock.rs")));
+        assert!(!filter.is_ignored(Path::new("vendor")));
+        assert!(!filter.is_ignored(Path::new("src/diagram.svg")));
+        assert!(IgnoreFilter::new(&["[".to_string()], &[]).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+
+/// Fewest stopword hits before the history is trusted to show a language.
+const MIN_HITS: usize = 8;
+/// Share of all hits the most frequent language needs.
+const MIN_SHARE: f64 = 0.6;
+
+/// Common short words that give a language away, chosen to overlap little.
+const STOPWORDS: [(&str, &[&str]); 7] = [
+    (
+        "English",
+        &[
+            "the", "and", "to", "of", "for", "with", "when", "from", "add", "fix", "remove",
+            "update", "use", "is", "on", "in", "instead",
+        ],
+    ),
+    (
+        "German",
+        &[
+            "der",
+            "die",
+            "das",
+            "und",
+            "nicht",
+            "mit",
+            "für",
+            "von",
+            "den",
+            "dem",
+            "beim",
+            "wird",
+            "ist",
+            "auf",
+            "zu",
+            "ein",
+            "eine",
+            "hinzufügen",
+            "entfernen",
+            "statt",
+        ],
+    ),
+    (
+        "French",
+        &[
+            "le", "les", "des", "et", "pour", "dans", "une", "du", "est", "avec", "sur", "pas",
+            "lors", "ajout", "ajoute", "ajouter", "supprime", "corrige", "au", "aux",
+        ],
+    ),
+    (
+        "Spanish",
+        &[
+            "el", "los", "las", "y", "para", "con", "una", "del", "por", "que", "se", "añadir",
+            "agregar", "corregir", "eliminar", "cuando",
+        ],
+    ),
+    (
+        "Portuguese",
+        &[
+            "os",
+            "para",
+            "com",
+            "uma",
+            "do",
+            "da",
+            "não",
+            "em",
+            "adicionar",
+            "adiciona",
+            "corrigir",
+            "corrige",
+            "quando",
+            "ao",
+        ],
+    ),
+    (
+        "Italian",
+        &[
+            "il", "gli", "per", "con", "una", "della", "non", "aggiungi", "aggiunge", "correggi",
+            "corregge", "rimuovi", "quando", "nel",
+        ],
+    ),
+    (
+        "Dutch",
+        &[
+            "het",
+            "en",
+            "van",
+            "voor",
+            "met",
+            "een",
+            "niet",
+            "toevoegen",
+            "verwijderen",
+            "bij",
+            "op",
+            "wordt",
+            "naar",
+        ],
+    ),
+];
+
+/// Expands a language code such as `de` to the name the model is told.
+pub(super) fn display_name(language: &str) -> String {
+    let name = match language.to_ascii_lowercase().as_str() {
+        "en" => "English",
+        "de" => "German",
+        "fr" => "French",
+        "es" => "Spanish",
+        "pt" => "Portuguese",
+        "it" => "Italian",
+        "nl" => "Dutch",
+        _ => language,
+    };
+    name.to_string()
+}
+
+/// Guesses the language recent commit messages are written in.
+///
+/// English is the model's default, so only other languages are returned, and
+/// only when the history clearly favours one of them.
+pub(super) fn detect<'m>(messages: impl IntoIterator<Item = &'m str>) -> Option<&'static str> {
+    let mut hits: HashMap<&'static str, usize> = HashMap::new();
+    for message in messages {
+        // Conventional Commit types and trailers are English whatever the language.
+        let mut lines = message.lines();
+        let subject = lines.next().unwrap_or_default();
+        let subject = subject.split_once(": ").map_or(subject, |(_, rest)| rest);
+        let body = lines.filter(|line| {
+            !line
+                .split_once(": ")
+                .is_some_and(|(token, _)| !token.is_empty() && !token.contains(' '))
+        });
+        let text = std::iter::once(subject)
+            .chain(body)
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase();
+        for word in text.split(|c: char| !c.is_alphabetic()) {
+            for (language, words) in STOPWORDS {
+                if words.contains(&word) {
+                    *hits.entry(language).or_default() += 1;
+                }
+            }
+        }
+    }
+
+    let total: usize = hits.values().sum();
+    let (language, count) = hits
+        .into_iter()
+        .max_by_key(|(language, count)| (*count, std::cmp::Reverse(*language)))?;
+    let confident = total >= MIN_HITS && count as f64 / total as f64 >= MIN_SHARE;
+    (confident && language != "English").then_some(language)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_language_is_detected() {
+        let german = [
+            "fix: Absturz beim Laden der Konfiguration beheben",
+            "feat(ui): Dialog für die Auswahl von Dateien hinzufügen\n\nDer Dialog ist nicht modal und wird mit Escape geschlossen.",
+            "refactor: Pfade mit dem neuen Modul auflösen",
+        ];
+        assert_eq!(detect(german), Some("German"));
+
+        let french = [
+            "fix: corrige le chargement des fichiers pour les dépôts vides",
+            "feat: ajoute une option pour le format des dates dans les rapports",
+        ];
+        assert_eq!(detect(french), Some("French"));
+
+        let english = [
+            "fix: handle the empty state when loading from disk",
+            "feat: add an option for the date format of the reports",
+        ];
+        assert_eq!(detect(english), None);
+        assert_eq!(detect(["chore: bump"]), None);
+        assert_eq!(display_name("de"), "German");
+        assert_eq!(display_name("Brazilian Portuguese"), "Brazilian Portuguese");
+    }
+}
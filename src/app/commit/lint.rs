@@ -0,0 +1,300 @@
+use anyhow::{Context, Result, anyhow};
+use clap::ValueEnum;
+use colored::Colorize;
+use regex::RegexBuilder;
+use serde::Serialize;
+use std::path::Path;
+use thiserror::Error;
+
+use super::conventional::{self, Violation};
+use super::seed::message_from_file;
+use super::{comment_char, trailers};
+use crate::config::{CommitSettings, ConventionalConfig, LintConfig};
+use crate::git::{range_commits, resolve_commit, resolve_range};
+
+/// How `shelf commit lint` reports its findings.
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum Format {
+    #[default]
+    Text,
+    Json,
+}
+
+/// A lint rule a message breaks.
+#[derive(Debug, PartialEq, Eq, Error)]
+pub(super) enum LintViolation {
+    #[error("message is empty")]
+    EmptyMessage,
+    #[error("subject is {0} characters long, the limit is {1}")]
+    SubjectTooLong(usize, usize),
+    #[error("subject must be followed by a blank line")]
+    MissingBlankLine,
+    #[error("body line {0} is {1} characters long, wrap at {2}")]
+    BodyLineTooLong(usize, usize, usize),
+    #[error("contains the forbidden word `{0}`")]
+    ForbiddenWord(String),
+    #[error("missing the `{0}:` trailer")]
+    MissingTrailer(String),
+    #[error(transparent)]
+    Conventional(Violation),
+}
+
+impl LintViolation {
+    /// Stable identifier of the rule, for machine-readable output.
+    fn rule(&self) -> &'static str {
+        match self {
+            Self::EmptyMessage => "empty-message",
+            Self::SubjectTooLong(..) => "subject-length",
+            Self::MissingBlankLine => "blank-line",
+            Self::BodyLineTooLong(..) => "body-wrap",
+            Self::ForbiddenWord(_) => "forbidden-word",
+            Self::MissingTrailer(_) => "required-trailer",
+            Self::Conventional(_) => "conventional",
+        }
+    }
+}
+
+/// One linted message and what it breaks.
+#[derive(Debug, Serialize)]
+struct Report {
+    /// Short hash, absent for messages read from a file.
+    commit: Option<String>,
+    subject: String,
+    violations: Vec<Finding>,
+}
+
+#[derive(Debug, Serialize)]
+struct Finding {
+    rule: &'static str,
+    message: String,
+}
+
+/// Lints a message file, a single commit or a range, prints the findings and
+/// fails when any message breaks a rule.
+pub(super) fn run(
+    settings: &CommitSettings,
+    range: Option<&str>,
+    file: Option<&Path>,
+    format: Format,
+) -> Result<()> {
+    let messages: Vec<(Option<String>, String)> = match file {
+        Some(path) => {
+            let content = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            vec![(None, message_from_file(&content, comment_char()))]
+        }
+        None => {
+            let spec = range.unwrap_or("HEAD");
+            let range = if spec.contains("..") {
+                resolve_range(spec)?
+            } else {
+                resolve_commit(spec)?
+            };
+            range_commits(range)?
+                .into_iter()
+                .map(|(oid, message)| (Some(oid.to_string()[..7].to_string()), message))
+                .collect()
+        }
+    };
+
+    let default_conventional = ConventionalConfig::default();
+    let conventional_rules = settings.lint.conventional.then(|| {
+        settings
+            .conventional
+            .as_ref()
+            .unwrap_or(&default_conventional)
+    });
+    let reports: Vec<Report> = messages
+        .into_iter()
+        .map(|(commit, message)| Report {
+            commit,
+            subject: message.lines().next().unwrap_or_default().to_string(),
+            violations: lint(&message, &settings.lint, conventional_rules)
+                .into_iter()
+                .map(|violation| Finding {
+                    rule: violation.rule(),
+                    message: violation.to_string(),
+                })
+                .collect(),
+        })
+        .collect();
+
+    match format {
+        Format::Json => println!("{}", serde_json::to_string_pretty(&reports)?),
+        Format::Text => print_reports(&reports),
+    }
+
+    let failing = reports
+        .iter()
+        .filter(|report| !report.violations.is_empty())
+        .count();
+    if failing > 0 {
+        return Err(anyhow!(
+            "{failing} of {} commit messages break the lint rules",
+            reports.len()
+        ));
+    }
+    Ok(())
+}
+
+/// Checks `message` against `rules`, and against the Conventional Commits
+/// format when `conventional` rules are given.
+pub(super) fn lint(
+    message: &str,
+    rules: &LintConfig,
+    conventional: Option<&ConventionalConfig>,
+) -> Vec<LintViolation> {
+    let mut violations = Vec::new();
+    let mut lines = message.trim_end().lines();
+    let Some(subject) = lines.next().filter(|subject| !subject.trim().is_empty()) else {
+        return vec![LintViolation::EmptyMessage];
+    };
+
+    let subject_length = subject.chars().count();
+    if subject_length > rules.max_subject_length {
+        violations.push(LintViolation::SubjectTooLong(
+            subject_length,
+            rules.max_subject_length,
+        ));
+    }
+    let body: Vec<&str> = lines.collect();
+    if rules.require_blank_line && body.first().is_some_and(|line| !line.trim().is_empty()) {
+        violations.push(LintViolation::MissingBlankLine);
+    }
+    for (index, line) in body.iter().enumerate() {
+        let length = line.chars().count();
+        // A line without spaces, such as a long URL, can't be wrapped.
+        if length > rules.max_body_line_length && line.trim().contains(' ') {
+            violations.push(LintViolation::BodyLineTooLong(
+                index + 2,
+                length,
+                rules.max_body_line_length,
+            ));
+        }
+    }
+
+    // `\b` only holds next to a word character, so words such as `fixup!`
+    // get a boundary on their alphanumeric side only.
+    let boundary = |c: Option<char>| match c {
+        Some(c) if c.is_alphanumeric() || c == '_' => r"\b",
+        _ => "",
+    };
+    for word in &rules.forbidden_words {
+        let pattern = format!(
+            "{}{}{}",
+            boundary(word.chars().next()),
+            regex::escape(word),
+            boundary(word.chars().last())
+        );
+        if let Ok(regex) = RegexBuilder::new(&pattern).case_insensitive(true).build()
+            && regex.is_match(message)
+        {
+            violations.push(LintViolation::ForbiddenWord(word.clone()));
+        }
+    }
+
+    let tokens = trailers::tokens(message);
+    for required in &rules.required_trailers {
+        if !tokens
+            .iter()
+            .any(|token| token.eq_ignore_ascii_case(required))
+        {
+            violations.push(LintViolation::MissingTrailer(required.clone()));
+        }
+    }
+
+    if let Some(rules) = conventional {
+        // Length and layout are already covered by the lint rules above.
+        violations.extend(
+            conventional::validate(message, rules)
+                .into_iter()
+                .filter(|violation| {
+                    !matches!(
+                        violation,
+                        Violation::HeaderTooLong(..)
+                            | Violation::MissingBlankLine
+                            | Violation::BodyLineTooLong(..)
+                    )
+                })
+                .map(LintViolation::Conventional),
+        );
+    }
+    violations
+}
+
+fn print_reports(reports: &[Report]) {
+    for report in reports {
+        let label = report.commit.as_deref().unwrap_or("message");
+        if report.violations.is_empty() {
+            println!(
+                "{} {} {}",
+                "✓".bright_green(),
+                label.dimmed(),
+                report.subject
+            );
+            continue;
+        }
+        println!("{} {} {}", "✗".bright_red(), label.dimmed(), report.subject);
+        for finding in &report.violations {
+            println!("    {} {}", finding.rule.bright_yellow(), finding.message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_are_checked_against_each_rule() {
+        let rules = LintConfig {
+            forbidden_words: vec!["wip".to_string()],
+            required_trailers: vec!["Signed-off-by".to_string()],
+            ..LintConfig::default()
+        };
+        let long_subject = "a".repeat(81);
+        let violations = lint(
+            &format!(
+                "{long_subject}\nbody right away, WIP and {}",
+                "x ".repeat(40)
+            ),
+            &rules,
+            None,
+        );
+        assert_eq!(
+            violations,
+            vec![
+                LintViolation::SubjectTooLong(81, 80),
+                LintViolation::MissingBlankLine,
+                LintViolation::BodyLineTooLong(2, 104, 80),
+                LintViolation::ForbiddenWord("wip".to_string()),
+                LintViolation::MissingTrailer("Signed-off-by".to_string()),
+            ]
+        );
+
+        let clean = "fix: wipe caches\n\nSee https://example.com/a/very/long/url/that/cannot/be/wrapped/at/all/ever\n\nSigned-off-by: A <a@example.com>";
+        assert!(lint(clean, &rules, Some(&ConventionalConfig::default())).is_empty());
+        assert_eq!(
+            lint(
+                "Fix caches\n\nSigned-off-by: A",
+                &rules,
+                Some(&ConventionalConfig::default())
+            ),
+            vec![LintViolation::Conventional(Violation::MalformedHeader)]
+        );
+        assert_eq!(
+            lint("\n\n", &rules, None),
+            vec![LintViolation::EmptyMessage]
+        );
+
+        let rules = LintConfig {
+            forbidden_words: vec!["fixup!".to_string()],
+            ..LintConfig::default()
+        };
+        assert_eq!(
+            lint("fixup! Add caches", &rules, None),
+            vec![LintViolation::ForbiddenWord("fixup!".to_string())]
+        );
+        assert!(lint("Add fixups", &rules, None).is_empty());
+    }
+}
@@ -0,0 +1,1099 @@
+pub(super) mod budget;
+mod candidates;
+pub(super) mod conventional;
+mod hook;
+pub(super) mod ignore;
+mod language;
+mod lint;
+mod seed;
+mod split;
+mod stage;
+mod state;
+mod structured;
+mod style;
+mod trailers;
+
+use anyhow::{Context, Result, anyhow};
+use clap::{Args, Subcommand};
+use colored::Colorize;
+use git2::Oid;
+use handlebars::Handlebars;
+use rig::client::builder::DynClientBuilder;
+use rig::completion::Prompt;
+use rig::providers::gemini::completion;
+use rig::providers::gemini::completion::gemini_api_types::{self};
+use serde_json::json;
+use std::collections::HashMap;
+use std::io::{IsTerminal, Write};
+use std::process::Command;
+use tempfile::NamedTempFile;
+
+use crate::config::{CommitSettings, ConventionalConfig, find_and_load_config};
+use crate::git::{
+    ChangeSource, FileChange, PendingOperation, amend_action, collect_file_changes, commit_action,
+    commit_history, current_branch, index_has_conflicts, pending_operation, range_messages,
+    resolve_commit, resolve_range, rewrite_range,
+};
+use crate::ui::{MenuOptions, UserAction, select_enabled, select_index, user_selection};
+use candidates::{Candidate, CandidateHistory, Origin};
+use conventional::Violation;
+use ignore::IgnoreFilter;
+use trailers::Trailer;
+
+const COMMIT_TEMPLATE_PATH: &str = "assets/prompts/commit_completion.hbs";
+const PREAMBLE_TEMPLATE_PATH: &str = "assets/prompts/commit_preamble.hbs";
+const SUMMARISE_TEMPLATE_PATH: &str = "assets/prompts/commit_summarise.hbs";
+
+const PROPOSED_HEADER: &str = "Proposed Commit Message:";
+const VIOLATIONS_HEADER: &str = "Commit rule violations:";
+const CANCELLED_TEXT: &str = "Operation cancelled.";
+
+const AI_TEMPERATURE: f64 = 0.2;
+const AI_MAX_TOKENS: u64 = 200;
+/// A structured message spends tokens on JSON keys and quoting.
+const MESSAGE_MAX_TOKENS: u64 = 512;
+/// Invalid structured replies are sent back to the model this many times.
+const MAX_REPAIRS: usize = 2;
+/// Files past this many in the overflow get a stat line instead of a summary request.
+const MAX_SUMMARISED_FILES: usize = 20;
+
+const EDITOR_ENV_VARS: [&str; 3] = ["GIT_EDITOR", "EDITOR", "VISUAL"];
+
+/// Summaries of overflowing files keyed by their patch, kept for the whole run
+/// so regenerating or requesting several candidates doesn't summarise again.
+type SummaryCache = tokio::sync::Mutex<HashMap<String, String>>;
+
+#[derive(Args)]
+pub struct CommitCMD {
+    #[command(subcommand)]
+    pub action: Option<CommitAction>,
+    /// Start of the message for the model to complete; defaults to a pending
+    /// merge or squash message, an unfinished message, or the commit template
+    #[arg(long)]
+    pub prefix: Option<String>,
+    /// AI model provider to use for generation
+    #[arg(short, long, default_value = "gemini")]
+    pub provider: String,
+    /// Specific model to use for commit message generation
+    #[arg(short, long, default_value = "gemini-2.5-flash-lite")]
+    pub model: String,
+    /// Number of previous commits to include as context
+    #[arg(long, short = 'd', default_value = "10")]
+    pub history_depth: usize,
+    /// Glob patterns of files to leave out of the diff and history sent to the model (comma-separated)
+    #[arg(short, long, default_value = None, value_delimiter = ',', num_args = 1..)]
+    pub ignored: Option<Vec<String>>,
+    /// Number of suggestions to request at once, sampled at rising temperatures
+    #[arg(
+        short = 'n',
+        long,
+        default_value = "1",
+        value_parser = clap::value_parser!(u8).range(1..=10),
+        conflicts_with_all = ["print", "yes", "split", "hook"]
+    )]
+    pub candidates: u8,
+    /// Trailer to append to the message, such as `Co-authored-by: Name <email>` (repeatable)
+    #[arg(long, value_name = "TOKEN: VALUE")]
+    pub trailer: Vec<String>,
+    /// Language to write the message in, such as `German` or `fr`; defaults to
+    /// `[commit] language`, then to the language of recent history
+    #[arg(long)]
+    pub language: Option<String>,
+    /// Show the effective ignore patterns and what they exclude, then exit
+    #[arg(long)]
+    pub explain: bool,
+    /// Skip the repository's pre-commit and commit-msg hooks
+    #[arg(long)]
+    pub no_verify: bool,
+    /// Print only the generated message to stdout without committing
+    #[arg(long, visible_alias = "dry-run", conflicts_with = "yes")]
+    pub print: bool,
+    /// Commit the first suggestion without prompting
+    #[arg(short, long)]
+    pub yes: bool,
+    /// Regenerate the message for HEAD from its changes plus the staged ones, and amend it
+    #[arg(long, conflicts_with_all = ["split", "hook"])]
+    pub amend: bool,
+    /// Pick unstaged hunks to stage, like `git add -p`, before generating the message
+    #[arg(long, conflicts_with = "hook")]
+    pub patch: bool,
+    /// Let the model group the staged hunks into several atomic commits
+    #[arg(long, conflicts_with = "hook")]
+    pub split: bool,
+    /// Fill a `prepare-commit-msg` message file non-interactively (used by the installed hook)
+    #[arg(long, value_names = ["MSG_FILE", "SOURCE", "SHA"], num_args = 1..=3)]
+    pub hook: Option<Vec<String>>,
+}
+
+#[derive(Subcommand)]
+pub enum CommitAction {
+    /// Manage the `prepare-commit-msg` hook that runs shelf from `git commit`.
+    Hook {
+        #[command(subcommand)]
+        action: hook::HookAction,
+    },
+    /// Generate a new message for an earlier commit and rewrite the history after it.
+    Reword {
+        /// The commit to reword
+        rev: String,
+    },
+    /// Show the commit style learned from the repository's history.
+    Style {
+        /// Analyse the history again instead of using the cached guide
+        #[arg(long)]
+        refresh: bool,
+    },
+    /// Check commit messages against the `[commit.lint]` rules.
+    ///
+    /// Works as a `commit-msg` hook with `shelf commit lint --file "$1"`.
+    Lint {
+        /// A commit, or a range `A..B`, to lint; defaults to HEAD
+        #[arg(conflicts_with = "file")]
+        range: Option<String>,
+        /// Lint the message in this file instead, as git passes it to `commit-msg`
+        #[arg(long, value_name = "PATH")]
+        file: Option<std::path::PathBuf>,
+        /// Output format
+        #[arg(long, value_enum, default_value_t)]
+        format: lint::Format,
+    },
+    /// Combine a range of commits into one with a message synthesised from theirs.
+    Squash {
+        /// `A..B`, or `A` for `A..HEAD`
+        range: String,
+    },
+}
+
+pub async fn run(args: CommitCMD) -> Result<()> {
+    match &args.action {
+        Some(CommitAction::Hook { action }) => return hook::run(action),
+        Some(CommitAction::Style { refresh }) => {
+            let settings = find_and_load_config()?.commit;
+            return style::show(*refresh, settings.conventional.is_some());
+        }
+        _ => {}
+    }
+
+    if args.patch {
+        if matches!(
+            args.action,
+            Some(CommitAction::Reword { .. } | CommitAction::Squash { .. })
+        ) {
+            return Err(anyhow!("--patch only applies to new or amended commits"));
+        }
+        ensure_interactive()?;
+        stage::select_and_stage()?;
+    }
+
+    let settings = find_and_load_config()?.commit;
+    if let Some(CommitAction::Lint {
+        range,
+        file,
+        format,
+    }) = &args.action
+    {
+        return lint::run(&settings, range.as_deref(), file.as_deref(), *format);
+    }
+    // The hook runs inside `git commit`, which handles the operation itself.
+    let operation = match args.hook {
+        Some(_) => None,
+        None => pending_operation()?,
+    };
+    let conflicts = operation.is_some() && index_has_conflicts()?;
+    state::check(operation, conflicts, &args)?;
+    let operation_context = state::context(operation, args.history_depth)?;
+    let ignore = IgnoreFilter::new(
+        &settings.ignore,
+        args.ignored.as_deref().unwrap_or_default(),
+    )?;
+    let (source, original_messages) = match &args.action {
+        Some(CommitAction::Reword { rev }) => {
+            let range = resolve_commit(rev)?;
+            (ChangeSource::Range(range), range_messages(range)?)
+        }
+        Some(CommitAction::Squash { range }) => {
+            let range = resolve_range(range)?;
+            let messages = range_messages(range)?;
+            if messages.len() < 2 {
+                return Err(anyhow!("Squashing needs a range of at least two commits"));
+            }
+            (ChangeSource::Range(range), messages)
+        }
+        _ if args.amend => (
+            ChangeSource::Amend,
+            range_messages(resolve_commit("HEAD")?)?,
+        ),
+        _ => (ChangeSource::Staged, Vec::new()),
+    };
+    let tickets = match (&settings.tickets, current_branch()?) {
+        (Some(ticket_config), Some(branch)) => trailers::ticket_ids(&branch, ticket_config)?,
+        _ => Vec::new(),
+    };
+    let trailers = trailers::collect(&settings, &tickets, &args.trailer)?;
+    // Complete what git or the team prepared, unless told what to start from.
+    let seed = match (&args.prefix, &args.hook, &source) {
+        (None, None, ChangeSource::Staged) => seed::seed_message()?,
+        _ => None,
+    };
+    if let Some((path, _)) = &seed {
+        eprintln!(
+            "{}",
+            format!("Completing the message in {}", path.display()).bright_blue()
+        );
+    }
+    let style = if settings.learn_style {
+        style::load(false)?.render(settings.conventional.is_some())
+    } else {
+        String::new()
+    };
+    let history = commit_history(&args.history_depth, |path| ignore.is_ignored(path))?;
+    let language = args
+        .language
+        .clone()
+        .or_else(|| settings.language.clone())
+        .or_else(|| {
+            language::detect(history.iter().map(|(_, message)| message.as_str()))
+                .map(str::to_string)
+        })
+        .map(|language| language::display_name(&language));
+    let summaries = SummaryCache::default();
+    let config = CommitConfig {
+        prefix: args
+            .prefix
+            .as_deref()
+            .or(seed.as_ref().map(|(_, text)| text.as_str())),
+        source,
+        original_messages: &original_messages,
+        tickets: &tickets,
+        trailers: &trailers,
+        history: &history,
+        style: &style,
+        language: language.as_deref(),
+        operation,
+        operation_context: &operation_context,
+        ..CommitConfig::new(&args, &settings, &ignore, &summaries)
+    };
+    if args.explain {
+        return explain_filters(&config);
+    }
+    if let Some(hook_args) = &args.hook {
+        return hook::fill_message_file(&config, hook_args).await;
+    }
+    if args.split {
+        return split::run(&config, args.print, args.yes).await;
+    }
+    match delivery(&args, std::io::stdin().is_terminal())? {
+        Delivery::Print => {
+            let (message, violations) = generate_valid_commit_message(&config).await?;
+            warn_violations(&violations);
+            let message = trailers::append(&message, config.trailers);
+            println!("{}", message.trim_end());
+            Ok(())
+        }
+        Delivery::Commit => {
+            let (message, violations) = generate_valid_commit_message(&config).await?;
+            if !violations.is_empty() {
+                display_proposed_message(&message, &violations);
+                return Err(anyhow!(
+                    "Refusing to commit a message that breaks the commit rules"
+                ));
+            }
+            let message = trailers::append(&message, config.trailers);
+            apply_message(&config, message)
+        }
+        Delivery::Interactive => execute_commit_workflow(&config).await,
+    }
+}
+
+/// What happens to the generated message.
+#[derive(Debug, PartialEq)]
+enum Delivery {
+    /// `--print`: written to stdout.
+    Print,
+    /// `--yes`: committed without prompting.
+    Commit,
+    /// Offered for review in the menu, which needs a terminal.
+    Interactive,
+}
+
+fn delivery(args: &CommitCMD, is_terminal: bool) -> Result<Delivery> {
+    if args.print {
+        Ok(Delivery::Print)
+    } else if args.yes {
+        Ok(Delivery::Commit)
+    } else {
+        require_terminal(is_terminal)?;
+        Ok(Delivery::Interactive)
+    }
+}
+
+/// Commits, amends or rewrites with the accepted message, depending on what it describes.
+fn apply_message(config: &CommitConfig<'_>, message: String) -> Result<()> {
+    match config.source {
+        ChangeSource::Staged => {
+            commit_action(message, config.verify)?;
+            if let Some(command) = state::continue_hint(config.operation) {
+                println!("{}", format!("Run `{command}` to carry on").bright_blue());
+            }
+        }
+        ChangeSource::Amend => {
+            amend_action(message, config.verify)?;
+        }
+        ChangeSource::Range(range) => {
+            let new_head = rewrite_range(range, &message, config.verify)?;
+            println!(
+                "{} {}",
+                "Rewrote history, HEAD is now".bright_green(),
+                &new_head.to_string()[..7]
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Fails instead of waiting on a prompt nobody can answer.
+pub(super) fn ensure_interactive() -> Result<()> {
+    require_terminal(std::io::stdin().is_terminal())
+}
+
+fn require_terminal(is_terminal: bool) -> Result<()> {
+    if is_terminal {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "stdin is not a terminal; use --print to output the message or --yes to commit it without prompting"
+        ))
+    }
+}
+
+/// Configuration context for commit message generation
+struct CommitConfig<'a> {
+    prefix: Option<&'a str>,
+    provider: &'a str,
+    model: &'a str,
+    history_depth: &'a usize,
+    ignore: &'a IgnoreFilter,
+    /// Recent commits from HEAD, without those touching only ignored paths.
+    history: &'a [(Oid, String)],
+    verify: bool,
+    /// Conventional Commits rules from `shelf.toml`, if enforced.
+    conventional: Option<&'a ConventionalConfig>,
+    /// Violations of the previous suggestion, fed back to the model.
+    violations: &'a [Violation],
+    /// Estimated tokens of staged changes the model may be sent.
+    token_budget: usize,
+    /// The changes the message describes.
+    source: ChangeSource,
+    /// Messages being replaced by `--amend`, `reword` or `squash`, oldest first.
+    original_messages: &'a [String],
+    /// Sampling temperature for the model.
+    temperature: f64,
+    /// Suggestions to request per generation in the interactive workflow.
+    candidates: usize,
+    /// Ticket IDs found in the current branch name.
+    tickets: &'a [String],
+    /// Trailers appended to the generated message.
+    trailers: &'a [Trailer],
+    /// Style guide learned from the repository's history, if enabled.
+    style: &'a str,
+    /// Language to write the message in; the model writes English without one.
+    language: Option<&'a str>,
+    /// The merge, cherry-pick, revert or rebase the commit concludes or continues.
+    operation: Option<PendingOperation>,
+    /// What the operation brings in, described for the model.
+    operation_context: &'a str,
+    /// Summaries of files that didn't fit the token budget.
+    summaries: &'a SummaryCache,
+}
+
+impl<'a> CommitConfig<'a> {
+    fn new(
+        cmd: &'a CommitCMD,
+        settings: &'a CommitSettings,
+        ignore: &'a IgnoreFilter,
+        summaries: &'a SummaryCache,
+    ) -> Self {
+        Self {
+            prefix: cmd.prefix.as_deref(),
+            provider: &cmd.provider,
+            model: &cmd.model,
+            history_depth: &cmd.history_depth,
+            ignore,
+            history: &[],
+            verify: !cmd.no_verify,
+            temperature: AI_TEMPERATURE,
+            candidates: usize::from(cmd.candidates),
+            conventional: settings.conventional.as_ref(),
+            violations: &[],
+            token_budget: settings.token_budget_for(&cmd.model),
+            source: ChangeSource::Staged,
+            original_messages: &[],
+            tickets: &[],
+            trailers: &[],
+            style: "",
+            language: None,
+            operation: None,
+            operation_context: "",
+            summaries,
+        }
+    }
+}
+
+/// Main commit workflow orchestrator
+async fn execute_commit_workflow(config: &CommitConfig<'_>) -> Result<()> {
+    // Every suggestion of the session, so earlier ones can be picked again.
+    let mut history = CandidateHistory::default();
+    // Trailers are kept out of the messages so each can be toggled on its own.
+    let mut enabled = vec![true; config.trailers.len()];
+
+    loop {
+        // Generate suggestions only when needed (first time or after regeneration)
+        if history.is_empty() {
+            generate_candidates(config, &mut history).await?;
+        }
+
+        let current = history.current();
+        let with_trailers = append_enabled_trailers(config, &current.message, &enabled);
+        display_proposed_message(&with_trailers, &current.violations);
+
+        let menu = MenuOptions {
+            trailers: !config.trailers.is_empty(),
+            history: history.len() > 1,
+            staging: !matches!(config.source, ChangeSource::Range(_)),
+        };
+        match user_selection(menu)? {
+            UserAction::RegenerateMessage => {
+                generate_candidates(config, &mut history).await?;
+            }
+            UserAction::EditWithEditor => {
+                let edited = edit_with_external_editor(&with_trailers)?;
+                enabled = config
+                    .trailers
+                    .iter()
+                    .map(|trailer| trailers::contains(&edited, trailer))
+                    .collect();
+                let current = history.current_mut();
+                current.message = trailers::strip(&edited, config.trailers);
+                current.violations = validate_message(config, &current.message);
+            }
+            UserAction::PickCandidate => {
+                pick_candidate(&mut history, "Go back to")?;
+            }
+            UserAction::MergeCandidates => {
+                merge_candidates(config, &mut history)?;
+            }
+            UserAction::StageChanges => {
+                if stage::select_and_stage()? {
+                    generate_candidates(config, &mut history).await?;
+                }
+            }
+            UserAction::ToggleTrailers => {
+                let items: Vec<String> = config.trailers.iter().map(Trailer::to_string).collect();
+                if let Some(selected) = select_enabled("Trailers to append", &items, &enabled)? {
+                    enabled = selected;
+                }
+            }
+            UserAction::CommitChanges => {
+                return apply_message(config, with_trailers);
+            }
+            UserAction::Quit | UserAction::Cancelled => {
+                display_cancellation_message();
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Requests a new batch of suggestions, letting the user choose when there are several.
+async fn generate_candidates(
+    config: &CommitConfig<'_>,
+    history: &mut CandidateHistory,
+) -> Result<()> {
+    let batch = candidates::generate(config, config.candidates).await?;
+    let several = batch.len() > 1;
+    history.extend(batch);
+    if several {
+        pick_candidate(history, "Pick a suggestion")?;
+    }
+    Ok(())
+}
+
+/// Shows every suggestion of the session and makes the chosen one current.
+fn pick_candidate(history: &mut CandidateHistory, prompt: &str) -> Result<()> {
+    if let Some(index) = select_index(prompt, &history.labels(), history.current_index())? {
+        history.select(index);
+    }
+    Ok(())
+}
+
+/// Opens two chosen suggestions together in the editor and keeps the result as a new one.
+fn merge_candidates(config: &CommitConfig<'_>, history: &mut CandidateHistory) -> Result<()> {
+    let labels = history.labels();
+    let Some(first) = select_index(
+        "First suggestion to merge",
+        &labels,
+        history.current_index(),
+    )?
+    else {
+        return Ok(());
+    };
+    let Some(second) = select_index("Second suggestion to merge", &labels, 0)? else {
+        return Ok(());
+    };
+    let (Some(first), Some(second)) = (history.get(first), history.get(second)) else {
+        return Ok(());
+    };
+
+    let comment = comment_char();
+    let draft = candidates::merge_draft(&first.message, &second.message, comment);
+    let (message, _) = split_comment_lines(&edit_with_external_editor(&draft)?, comment);
+    if message.trim().is_empty() {
+        return Ok(());
+    }
+    let violations = validate_message(config, &message);
+    history.extend(vec![Candidate {
+        message,
+        violations,
+        origin: Origin::Merged,
+    }]);
+    Ok(())
+}
+
+/// Checks a hand-edited message against the configured commit rules.
+fn validate_message(config: &CommitConfig<'_>, message: &str) -> Vec<Violation> {
+    config
+        .conventional
+        .map(|rules| conventional::validate(message, rules))
+        .unwrap_or_default()
+}
+
+/// Appends the trailers the user left enabled to `message`.
+fn append_enabled_trailers(config: &CommitConfig<'_>, message: &str, enabled: &[bool]) -> String {
+    let selected = config
+        .trailers
+        .iter()
+        .zip(enabled)
+        .filter(|(_, enabled)| **enabled)
+        .map(|(trailer, _)| trailer);
+    trailers::append(message, selected)
+}
+
+/// Generate a commit message that satisfies the configured Conventional Commits rules.
+///
+/// Messages that break the rules are repaired in place where possible and
+/// regenerated otherwise; violations that survive every attempt are returned
+/// alongside the last message.
+async fn generate_valid_commit_message(
+    config: &CommitConfig<'_>,
+) -> Result<(String, Vec<Violation>)> {
+    let message = generate_commit_message(config).await?;
+    let Some(rules) = config.conventional else {
+        return Ok((message, Vec::new()));
+    };
+
+    let mut message = conventional::repair(&message, rules);
+    let mut violations = conventional::validate(&message, rules);
+    for _ in 0..rules.max_regenerations {
+        if violations.is_empty() {
+            break;
+        }
+        let retry = CommitConfig {
+            violations: &violations,
+            ..*config
+        };
+        message = conventional::repair(&generate_commit_message(&retry).await?, rules);
+        violations = conventional::validate(&message, rules);
+    }
+    Ok((message, violations))
+}
+
+/// Generate commit message using AI model
+///
+/// The model replies with a structured message, which is checked and
+/// assembled here; replies that don't fit the schema are sent back for repair.
+async fn generate_commit_message(config: &CommitConfig<'_>) -> Result<String> {
+    let prompt = build_commit_prompt(config).await?;
+    let client = create_client(config, MESSAGE_MAX_TOKENS)?;
+    let mut reply = response_text(
+        client
+            .prompt(prompt.clone())
+            .await
+            .map_err(|e| anyhow!(e))?,
+    );
+
+    let mut repairs = 0;
+    loop {
+        let message = structured::parse(&reply).and_then(|message| {
+            let message = message.assemble();
+            structured::check_prefix(&message, config.prefix)?;
+            Ok(message)
+        });
+        match message {
+            Ok(message) => return Ok(message),
+            Err(error) if repairs < MAX_REPAIRS => {
+                repairs += 1;
+                let repair = structured::repair_prompt(&prompt, &reply, &error);
+                reply = response_text(client.prompt(repair).await.map_err(|e| anyhow!(e))?);
+            }
+            Err(error) => {
+                return Err(error.context("The model did not return a valid commit message"));
+            }
+        }
+    }
+}
+
+/// Unwrap the model's text from a raw response
+pub(super) fn response_text(response: String) -> String {
+    // Try to parse as Gemini API response first, fall back to raw text
+    if let Ok(parsed_response) =
+        serde_json::from_str::<gemini_api_types::GenerateContentResponse>(&response)
+    {
+        extract_text_from_gemini_response(&parsed_response)
+    } else {
+        response
+    }
+}
+
+/// Render the commit message prompt for the changes being described
+async fn build_commit_prompt(config: &CommitConfig<'_>) -> Result<String> {
+    let diff_content = collect_budgeted_changes(config).await?;
+
+    validate_diff_content(&diff_content)?;
+
+    let commit_history = build_commit_history(config)?;
+    build_prompt_from_template(config, &diff_content, &commit_history)
+}
+
+/// Collect the staged changes, condensed to fit the model's token budget
+async fn collect_budgeted_changes(config: &CommitConfig<'_>) -> Result<String> {
+    let mut changes =
+        collect_file_changes(config.source).context("Failed to retrieve staged changes")?;
+    changes.retain(|change| !config.ignore.is_ignored(&change.path));
+    let budgeted = budget::fit_to_budget(changes, config.token_budget);
+    if budgeted.overflow.is_empty() {
+        return Ok(budgeted.diff);
+    }
+
+    // Held while summarising, so concurrent candidates wait for the first
+    // one's summaries instead of requesting their own.
+    let mut cache = config.summaries.lock().await;
+    let uncached = budgeted
+        .overflow
+        .iter()
+        .take(MAX_SUMMARISED_FILES)
+        .filter(|change| !cache.contains_key(&change.patch))
+        .count();
+    if uncached > 0 {
+        eprintln!(
+            "{}",
+            format!(
+                "Staged changes exceed the {} token budget, summarising {uncached} files separately...",
+                config.token_budget
+            )
+            .bright_blue()
+        );
+    }
+    let mut summaries = String::new();
+    for (index, change) in budgeted.overflow.iter().enumerate() {
+        if index >= MAX_SUMMARISED_FILES {
+            summaries.push_str(&budget::stat_line(change, "not summarised"));
+            continue;
+        }
+        let summary = match cache.get(&change.patch) {
+            Some(summary) => summary.clone(),
+            None => {
+                let summary = summarise_file_change(config, change).await?;
+                cache.insert(change.patch.clone(), summary.clone());
+                summary
+            }
+        };
+        summaries.push_str(&format!(
+            " {} | +{} -{}: {}\n",
+            change.path.display(),
+            change.insertions,
+            change.deletions,
+            summary.trim()
+        ));
+    }
+
+    Ok(format!(
+        "{}\nSummaries of further changed files:\n{summaries}",
+        budgeted.diff
+    ))
+}
+
+/// Ask the model for a short summary of one file's changes
+async fn summarise_file_change(config: &CommitConfig<'_>, change: &FileChange) -> Result<String> {
+    let template = load_template_with_fallback(SUMMARISE_TEMPLATE_PATH)?;
+    let data = json!({
+        "FILE_PATH": change.path.display().to_string(),
+        "FILE_CHANGES": budget::truncate_to_tokens(&change.patch, config.token_budget),
+    });
+    let prompt = Handlebars::new()
+        .render_template(&template, &data)
+        .context("Failed to render summary prompt template")?;
+
+    let client = create_client(config, AI_MAX_TOKENS)?;
+    let response = client.prompt(prompt).await.map_err(|e| anyhow!(e))?;
+    Ok(response_text(response))
+}
+
+/// Create and configure AI client for commit message generation
+fn create_client(config: &CommitConfig<'_>, max_tokens: u64) -> Result<impl Prompt> {
+    let client_builder = DynClientBuilder::new();
+    let mut preamble_content = load_template_with_fallback(PREAMBLE_TEMPLATE_PATH)?;
+    if let Some(language) = config.language {
+        preamble_content.push_str(&language_instruction(language));
+    }
+    let agent = client_builder
+        .agent(config.provider, config.model)?
+        .preamble(&preamble_content)
+        .temperature(config.temperature)
+        .max_tokens(max_tokens)
+        .build();
+
+    Ok(agent)
+}
+
+/// Extract text content from Gemini API response
+fn extract_text_from_gemini_response(
+    response: &completion::gemini_api_types::GenerateContentResponse,
+) -> String {
+    response
+        .candidates
+        .first()
+        .map(|candidate| candidate.content.parts.first())
+        .and_then(|part| match &part.part {
+            gemini_api_types::PartKind::Text(text) => Some(text.clone()),
+            _ => None,
+        })
+        .unwrap_or_else(|| "No commit message generated".to_string())
+}
+
+/// Build the complete prompt from template and context data
+fn build_prompt_from_template(
+    config: &CommitConfig<'_>,
+    diff_content: &str,
+    commit_history: &str,
+) -> Result<String> {
+    let template_content = load_template_with_fallback(COMMIT_TEMPLATE_PATH)?;
+    let template_data = create_template_data(config, diff_content, commit_history)?;
+
+    let handlebars = Handlebars::new();
+    handlebars
+        .render_template(&template_content, &template_data)
+        .context("Failed to render prompt template")
+}
+
+/// Create template data for Handlebars rendering
+fn create_template_data(
+    config: &CommitConfig<'_>,
+    diff_content: &str,
+    commit_history: &str,
+) -> Result<serde_json::Value> {
+    let partial_commit_section = config
+        .prefix
+        .filter(|p| !p.is_empty())
+        .map(|p| format!("PARTIAL_COMMIT_MESSAGE:\n```\n{}\n```\n", p.trim_end()))
+        .unwrap_or_default();
+
+    let commit_rules = config
+        .conventional
+        .map(|rules| conventional::prompt_rules(rules, config.violations))
+        .unwrap_or_default();
+
+    let original_messages_section = match config.original_messages {
+        [] => String::new(),
+        [message] => format!(
+            "ORIGINAL_COMMIT_MESSAGE (write a better message for the same changes):\n```\n{}\n```\n",
+            message.trim_end()
+        ),
+        messages => format!(
+            "COMMIT_MESSAGES_TO_COMBINE (write one message covering all of them):\n```\n{}\n```\n",
+            messages
+                .iter()
+                .map(|message| message.trim_end())
+                .collect::<Vec<_>>()
+                .join("\n---\n")
+        ),
+    };
+
+    Ok(json!({
+        "MESSAGE_SCHEMA": structured::schema()?,
+        "CODE_CHANGES": format!("```diff\n{diff_content}\n```"),
+        "COMMIT_HISTORY": format!("```\n{commit_history}\n```\n"),
+        "PARTIAL_COMMIT_MESSAGE": partial_commit_section,
+        "ORIGINAL_MESSAGES": original_messages_section,
+        "OPERATION": config.operation_context,
+        "COMMIT_RULES": commit_rules,
+        "COMMIT_STYLE": config.style,
+        "TICKET": config.tickets.join(", "),
+        "LANGUAGE": config.language.map(language_line).unwrap_or_default()
+    }))
+}
+
+/// Tells the model to write in `language` while keeping the machine-read parts in English.
+fn language_instruction(language: &str) -> String {
+    format!(
+        "\nWrite commit messages and change summaries in {language}. Keep Conventional \
+         Commit types such as `feat` or `fix`, scopes, code identifiers and trailer tokens \
+         in English.\n"
+    )
+}
+
+/// Names the language in the prompt, next to the style the message follows.
+fn language_line(language: &str) -> String {
+    format!("LANGUAGE: {language}\n")
+}
+
+/// Load a template from file, trying the current directory first, then a fallback user config directory.
+pub(super) fn load_template_with_fallback(template_path: &str) -> Result<String> {
+    let current_dir_path = std::path::PathBuf::from(template_path);
+
+    // Try reading from the current directory first
+    match std::fs::read_to_string(&current_dir_path) {
+        Ok(content) => Ok(content),
+        Err(e_current) => {
+            // If that fails, try the user's config directory
+            let home_var = if cfg!(target_os = "windows") {
+                "USERPROFILE"
+            } else {
+                "HOME"
+            };
+            let home_dir = std::env::var(home_var)
+                .with_context(|| format!("{home_var} environment variable not found"))?;
+
+            let config_dir_path = std::path::PathBuf::from(home_dir)
+                .join(".config")
+                .join("shelf")
+                .join(template_path);
+
+            match std::fs::read_to_string(&config_dir_path) {
+                Ok(content) => Ok(content),
+                Err(e_config) => Err(anyhow!(
+                    "Failed to read template from:\n  - Current directory: {:?} ({})\n  - Config directory: {:?} ({})",
+                    current_dir_path,
+                    e_current,
+                    config_dir_path,
+                    e_config
+                )),
+            }
+        }
+    }
+}
+
+/// Strips a Markdown code fence the model may wrap a JSON reply in.
+pub(super) fn strip_code_fence(response: &str) -> &str {
+    let trimmed = response.trim();
+    trimmed
+        .strip_prefix("```json")
+        .or_else(|| trimmed.strip_prefix("```"))
+        .and_then(|rest| rest.trim_end().strip_suffix("```"))
+        .unwrap_or(trimmed)
+}
+
+/// Build formatted commit history string
+fn build_commit_history(config: &CommitConfig<'_>) -> Result<String> {
+    if config.history.is_empty() {
+        return Ok(String::new());
+    }
+
+    let formatted_history = config
+        .history
+        .iter()
+        .map(|(oid, message)| {
+            format!(
+                "• {}: {}",
+                &oid.to_string()[..7],
+                message.lines().next().unwrap_or("")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Ok(format!("COMMIT_HISTORY:\n{formatted_history}"))
+}
+
+/// Print the effective ignore patterns with the staged files and history commits they exclude
+fn explain_filters(config: &CommitConfig<'_>) -> Result<()> {
+    config.ignore.explain();
+
+    println!("\n{}", "Staged files left out:".bold());
+    let staged =
+        collect_file_changes(ChangeSource::Staged).context("Failed to retrieve staged changes")?;
+    let excluded: Vec<_> = staged
+        .iter()
+        .filter(|change| config.ignore.is_ignored(&change.path))
+        .collect();
+    if excluded.is_empty() {
+        println!("  {}", "(none)".dimmed());
+    }
+    for change in excluded {
+        println!("  {}", change.path.display());
+    }
+
+    println!(
+        "\n{}",
+        format!(
+            "History commits left out (of the last {}):",
+            config.history_depth
+        )
+        .bold()
+    );
+    let dropped: Vec<_> = commit_history(config.history_depth, |_| false)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|(oid, _)| !config.history.iter().any(|(kept, _)| kept == oid))
+        .collect();
+    if dropped.is_empty() {
+        println!("  {}", "(none)".dimmed());
+    }
+    for (oid, message) in dropped {
+        println!(
+            "  {} {}",
+            &oid.to_string()[..7],
+            message.lines().next().unwrap_or_default()
+        );
+    }
+    Ok(())
+}
+
+/// Returns the character git uses to start comment lines in commit messages.
+fn comment_char() -> char {
+    git2::Repository::open(".")
+        .and_then(|repo| repo.config())
+        .and_then(|config| config.get_string("core.commentChar"))
+        .ok()
+        // "auto" only changes anything when a message already uses '#'.
+        .filter(|value| value != "auto")
+        .and_then(|value| value.chars().next())
+        .unwrap_or('#')
+}
+
+/// Splits a message file into its text and its comment lines, in that order.
+fn split_comment_lines(content: &str, comment: char) -> (String, String) {
+    let (comments, text): (Vec<&str>, Vec<&str>) =
+        content.lines().partition(|line| line.starts_with(comment));
+    let text = text.join("\n").trim_end().to_string();
+    let comments = comments.iter().map(|line| format!("{line}\n")).collect();
+    (text, comments)
+}
+
+/// Validate that there are staged changes to commit
+fn validate_diff_content(diff: &str) -> Result<()> {
+    if diff.trim().is_empty() {
+        Err(anyhow!("Cannot generate commit message from empty diff"))
+    } else {
+        Ok(())
+    }
+}
+
+/// Display the proposed commit message to the user, with any rule violations
+fn display_proposed_message(message: &str, violations: &[Violation]) {
+    println!("\n{PROPOSED_HEADER}\n{}", message.bright_yellow());
+    if !violations.is_empty() {
+        println!("\n{}", VIOLATIONS_HEADER.bright_red());
+        for violation in violations {
+            println!("  • {violation}");
+        }
+    }
+}
+
+/// Report rule violations on stderr, keeping stdout for the message itself
+fn warn_violations(violations: &[Violation]) {
+    for violation in violations {
+        eprintln!("{} {violation}", "warning:".bright_yellow().bold());
+    }
+}
+
+/// Display cancellation message
+fn display_cancellation_message() {
+    println!("{}", CANCELLED_TEXT.bright_blue());
+}
+
+/// Open external editor for commit message editing
+fn edit_with_external_editor(initial_message: &str) -> Result<String> {
+    let temp_file = create_temp_file_with_content(initial_message)?;
+    let editor = determine_editor();
+
+    launch_editor(&editor, temp_file.path())?;
+    read_edited_content(temp_file.path())
+}
+
+/// Create temporary file with initial content
+fn create_temp_file_with_content(content: &str) -> Result<NamedTempFile> {
+    let mut temp_file = NamedTempFile::new().context("Failed to create temporary file")?;
+    temp_file
+        .write_all(content.as_bytes())
+        .context("Failed to write content to temporary file")?;
+    Ok(temp_file)
+}
+
+/// Determine which editor to use based on environment variables
+pub(super) fn determine_editor() -> String {
+    EDITOR_ENV_VARS
+        .iter()
+        .find_map(|&key| std::env::var(key).ok())
+        .unwrap_or_else(default_editor)
+}
+
+/// Get platform-specific default editor
+fn default_editor() -> String {
+    if cfg!(target_os = "windows") {
+        "notepad".to_string()
+    } else {
+        "vi".to_string()
+    }
+}
+
+/// Launch the chosen editor with the specified file
+pub(super) fn launch_editor(editor: &str, file_path: &std::path::Path) -> Result<()> {
+    let status = Command::new(editor)
+        .arg(file_path)
+        .status()
+        .with_context(|| format!("Failed to launch editor: {editor}"))?;
+
+    if !status.success() {
+        return Err(anyhow!("Editor exited with non-zero status"));
+    }
+    Ok(())
+}
+
+/// Read the edited content from the temporary file
+fn read_edited_content(file_path: &std::path::Path) -> Result<String> {
+    std::fs::read_to_string(file_path).context("Failed to read edited content from temporary file")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        commit: CommitCMD,
+    }
+
+    #[test]
+    fn non_interactive_modes_are_parsed_and_required_without_a_terminal() {
+        let parse = |args: &[&str]| {
+            Cli::try_parse_from(std::iter::once("shelf").chain(args.iter().copied()))
+                .map(|cli| cli.commit)
+        };
+        assert!(parse(&["--print", "--yes"]).is_err());
+
+        let print = parse(&["--dry-run"]).expect("--dry-run is --print");
+        assert_eq!(delivery(&print, false).ok(), Some(Delivery::Print));
+        let yes = parse(&["-y"]).expect("-y is --yes");
+        assert_eq!(delivery(&yes, false).ok(), Some(Delivery::Commit));
+
+        let interactive = parse(&[]).expect("no flags");
+        assert_eq!(
+            delivery(&interactive, true).ok(),
+            Some(Delivery::Interactive)
+        );
+        let err = delivery(&interactive, false).unwrap_err().to_string();
+        assert!(err.contains("--print") && err.contains("--yes"));
+    }
+}
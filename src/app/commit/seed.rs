@@ -0,0 +1,54 @@
+use anyhow::Result;
+use std::path::PathBuf;
+
+use super::comment_char;
+use crate::git::pending_message_files;
+
+/// Everything below this line of a message file is dropped by git.
+const SCISSORS: &str = "------------------------ >8 ------------------------";
+
+/// Finds a message to complete when no `--prefix` is given: a pending merge,
+/// cherry-pick or squash message, an unfinished commit message, or the
+/// commit template, with comments stripped.
+pub(super) fn seed_message() -> Result<Option<(PathBuf, String)>> {
+    let comment = comment_char();
+    for (path, content) in pending_message_files()? {
+        let text = message_from_file(&content, comment);
+        if text.is_empty() {
+            continue;
+        }
+        return Ok(Some((path, text)));
+    }
+    Ok(None)
+}
+
+/// The message git would record from a message file: comments and anything
+/// below the scissors line removed.
+pub(super) fn message_from_file(content: &str, comment: char) -> String {
+    let content = match content.find(&format!("{comment} {SCISSORS}")) {
+        Some(index) => &content[..index],
+        None => content,
+    };
+    let text: Vec<&str> = content
+        .lines()
+        .filter(|line| !line.starts_with(comment))
+        .collect();
+    text.join("\n").trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_files_drop_comments_and_the_verbose_diff() {
+        let merge = "Merge branch 'feature'\n\n# Conflicts:\n#\tsrc/main.rs\n";
+        assert_eq!(message_from_file(merge, '#'), "Merge branch 'feature'");
+
+        let verbose = format!(
+            "feat: add x\n\n# Please enter the commit message\n# {SCISSORS}\ndiff --git a/x b/x\n"
+        );
+        assert_eq!(message_from_file(&verbose, '#'), "feat: add x");
+        assert_eq!(message_from_file("; only a comment\n", ';'), "");
+    }
+}
@@ -0,0 +1,225 @@
+use anyhow::{Context, Result, anyhow};
+use colored::Colorize;
+use dialoguer::{Confirm, theme::ColorfulTheme};
+use handlebars::Handlebars;
+use rig::completion::Prompt;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashSet;
+
+use super::conventional::{self, Violation};
+use super::trailers;
+use super::{
+    CommitConfig, build_commit_history, create_client, display_cancellation_message,
+    ensure_interactive, language_line, load_template_with_fallback, response_text,
+    strip_code_fence,
+};
+use crate::git::{Hunk, commit_hunk_groups, staged_hunks};
+
+const SPLIT_TEMPLATE_PATH: &str = "assets/prompts/commit_split.hbs";
+
+/// Plans list every hunk and several messages, so they need far more room than one message.
+const SPLIT_MAX_TOKENS: u64 = 4096;
+
+/// The model's proposal for splitting the staged changes.
+#[derive(Debug, Deserialize, JsonSchema)]
+struct SplitPlan {
+    /// Commits to create, in order.
+    commits: Vec<PlannedCommit>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct PlannedCommit {
+    /// Complete commit message.
+    message: String,
+    /// Numbers of the hunks making up this commit.
+    hunks: Vec<usize>,
+}
+
+/// A planned commit after checking it against the staged hunks and the commit rules.
+struct ReviewedCommit {
+    message: String,
+    hunks: Vec<usize>,
+    violations: Vec<Violation>,
+}
+
+/// Proposes a split of the staged hunks into atomic commits and creates them once approved.
+pub(super) async fn run(config: &CommitConfig<'_>, print: bool, yes: bool) -> Result<()> {
+    let hunks = staged_hunks()?;
+    if hunks.is_empty() {
+        return Err(anyhow!("Cannot generate commit message from empty diff"));
+    }
+
+    let plan = request_plan(config, &hunks).await?;
+    let commits = review_plan(plan, &hunks, config);
+    if commits.is_empty() {
+        return Err(anyhow!("The model did not assign any hunk to a commit"));
+    }
+    display_plan(&commits, &hunks);
+
+    if print {
+        return Ok(());
+    }
+    if commits.iter().any(|commit| !commit.violations.is_empty()) && yes {
+        return Err(anyhow!(
+            "Refusing to commit messages that break the commit rules"
+        ));
+    }
+    if !yes {
+        ensure_interactive()?;
+        let confirmed = Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("Create these {} commits?", commits.len()))
+            .default(true)
+            .interact()?;
+        if !confirmed {
+            display_cancellation_message();
+            return Ok(());
+        }
+    }
+
+    let groups: Vec<(String, Vec<usize>)> = commits
+        .into_iter()
+        .map(|commit| (commit.message, commit.hunks))
+        .collect();
+    commit_hunk_groups(&groups, config.verify)
+}
+
+async fn request_plan(config: &CommitConfig<'_>, hunks: &[Hunk]) -> Result<SplitPlan> {
+    let template = load_template_with_fallback(SPLIT_TEMPLATE_PATH)?;
+    let schema = serde_json::to_string_pretty(&schemars::schema_for!(SplitPlan))?;
+    let commit_rules = config
+        .conventional
+        .map(|rules| conventional::prompt_rules(rules, &[]))
+        .unwrap_or_default();
+
+    let data = json!({
+        "PLAN_SCHEMA": schema,
+        "HUNKS": format_hunks(hunks),
+        "COMMIT_HISTORY": build_commit_history(config)?,
+        "COMMIT_RULES": commit_rules,
+        "COMMIT_STYLE": config.style,
+        "LANGUAGE": config.language.map(language_line).unwrap_or_default(),
+    });
+    let prompt = Handlebars::new()
+        .render_template(&template, &data)
+        .context("Failed to render split prompt template")?;
+
+    let client = create_client(config, SPLIT_MAX_TOKENS)?;
+    let response = client.prompt(prompt).await.map_err(|e| anyhow!(e))?;
+    parse_plan(&response_text(response))
+}
+
+fn format_hunks(hunks: &[Hunk]) -> String {
+    hunks
+        .iter()
+        .map(|hunk| {
+            format!(
+                "HUNK {} in {}:\n```diff\n{}\n{}```\n",
+                hunk.id,
+                hunk.path.display(),
+                hunk.header,
+                hunk.patch
+            )
+        })
+        .collect()
+}
+
+/// Parses the model's JSON reply, tolerating a surrounding Markdown code fence.
+fn parse_plan(response: &str) -> Result<SplitPlan> {
+    serde_json::from_str(strip_code_fence(response))
+        .context("The model did not return a valid split plan")
+}
+
+/// Drops unknown and repeated hunk numbers and empty commits, and applies the
+/// Conventional Commits repairs and checks to every message.
+fn review_plan(plan: SplitPlan, hunks: &[Hunk], config: &CommitConfig<'_>) -> Vec<ReviewedCommit> {
+    let known: HashSet<usize> = hunks.iter().map(|hunk| hunk.id).collect();
+    let mut claimed = HashSet::new();
+
+    plan.commits
+        .into_iter()
+        .filter_map(|commit| {
+            let hunks: Vec<usize> = commit
+                .hunks
+                .into_iter()
+                .filter(|id| known.contains(id) && claimed.insert(*id))
+                .collect();
+            if hunks.is_empty() {
+                return None;
+            }
+
+            let (message, violations) = match config.conventional {
+                Some(rules) => {
+                    let message = conventional::repair(&commit.message, rules);
+                    let violations = conventional::validate(&message, rules);
+                    (message, violations)
+                }
+                None => (commit.message.trim().to_string(), Vec::new()),
+            };
+            let message = trailers::append(&message, config.trailers);
+            Some(ReviewedCommit {
+                message,
+                hunks,
+                violations,
+            })
+        })
+        .collect()
+}
+
+fn display_plan(commits: &[ReviewedCommit], hunks: &[Hunk]) {
+    let describe = |id: &usize| {
+        hunks
+            .iter()
+            .find(|hunk| hunk.id == *id)
+            .map(|hunk| format!("    #{id} {} {}", hunk.path.display(), hunk.header))
+            .unwrap_or_default()
+    };
+
+    println!("\n{}", "Proposed Commits:".bold());
+    for (index, commit) in commits.iter().enumerate() {
+        println!(
+            "\n{} {}",
+            format!("[{}/{}]", index + 1, commits.len()).bright_blue(),
+            commit.message.bright_yellow()
+        );
+        for id in &commit.hunks {
+            println!("{}", describe(id).dimmed());
+        }
+        for violation in &commit.violations {
+            println!("    {} {violation}", "✗".bright_red());
+        }
+    }
+
+    let assigned: HashSet<usize> = commits
+        .iter()
+        .flat_map(|commit| commit.hunks.iter().copied())
+        .collect();
+    let unassigned: Vec<&Hunk> = hunks
+        .iter()
+        .filter(|hunk| !assigned.contains(&hunk.id))
+        .collect();
+    if !unassigned.is_empty() {
+        println!("\n{}", "Left staged:".bold());
+        for hunk in unassigned {
+            println!("{}", describe(&hunk.id).dimmed());
+        }
+    }
+    println!();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plan_parses_from_fenced_json() -> Result<()> {
+        let response =
+            "```json\n{\"commits\": [{\"message\": \"fix: a\", \"hunks\": [2, 1]}]}\n```";
+        let plan = parse_plan(response)?;
+        assert_eq!(plan.commits.len(), 1);
+        assert_eq!(plan.commits[0].hunks, vec![2, 1]);
+        assert!(parse_plan("Here is the plan").is_err());
+        Ok(())
+    }
+}
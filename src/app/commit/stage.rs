@@ -0,0 +1,140 @@
+use anyhow::Result;
+use colored::Colorize;
+use dialoguer::{Select, theme::ColorfulTheme};
+use std::path::Path;
+
+use crate::git::{Hunk, stage_hunks, unstaged_hunks};
+
+/// What to do with the hunk on screen, in the order the choices are offered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Choice {
+    Stage,
+    Skip,
+    StageRestOfFile,
+    SkipRestOfFile,
+    Finish,
+}
+
+const CHOICES: [(&str, Choice); 5] = [
+    ("Stage this hunk", Choice::Stage),
+    ("Skip this hunk", Choice::Skip),
+    (
+        "Stage this and the rest of the file",
+        Choice::StageRestOfFile,
+    ),
+    ("Skip the rest of the file", Choice::SkipRestOfFile),
+    ("Done, skip everything left", Choice::Finish),
+];
+
+/// Walks the unstaged hunks one at a time, like `git add -p`, and stages the
+/// chosen ones. Returns whether anything was staged.
+pub(super) fn select_and_stage() -> Result<bool> {
+    let hunks = unstaged_hunks()?;
+    if hunks.is_empty() {
+        println!("{}", "No unstaged changes to tracked files.".bright_blue());
+        return Ok(false);
+    }
+
+    let paths: Vec<&Path> = hunks.iter().map(|hunk| hunk.path.as_path()).collect();
+    let picked = walk_hunks(&paths, |index| {
+        display_hunk(&hunks[index], index + 1, hunks.len());
+        let selection = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("Stage this hunk?")
+            .items(&CHOICES.map(|(label, _)| label))
+            .default(0)
+            .interact_opt()?;
+        Ok(selection.map(|index| CHOICES[index].1))
+    })?;
+    let ids: Vec<usize> = picked.into_iter().map(|index| hunks[index].id).collect();
+    if ids.is_empty() {
+        return Ok(false);
+    }
+    stage_hunks(&ids)?;
+    println!(
+        "{}",
+        format!("Staged {} of {} hunks.", ids.len(), hunks.len()).bright_green()
+    );
+    Ok(true)
+}
+
+/// Asks `choose` about each hunk, given the path of every hunk in order, and
+/// returns the positions of those to stage. A "rest of the file" choice
+/// settles the file's remaining hunks without asking again; a cancelled
+/// prompt ends the walk like `Finish`.
+fn walk_hunks(
+    paths: &[&Path],
+    mut choose: impl FnMut(usize) -> Result<Option<Choice>>,
+) -> Result<Vec<usize>> {
+    let mut picked = Vec::new();
+    let mut settled: Option<(&Path, bool)> = None;
+
+    for (index, &path) in paths.iter().enumerate() {
+        if let Some((settled_path, stage)) = settled {
+            if settled_path == path {
+                if stage {
+                    picked.push(index);
+                }
+                continue;
+            }
+            settled = None;
+        }
+        match choose(index)? {
+            Some(Choice::Stage) => picked.push(index),
+            Some(Choice::Skip) => {}
+            Some(Choice::StageRestOfFile) => {
+                picked.push(index);
+                settled = Some((path, true));
+            }
+            Some(Choice::SkipRestOfFile) => settled = Some((path, false)),
+            Some(Choice::Finish) | None => break,
+        }
+    }
+    Ok(picked)
+}
+
+fn display_hunk(hunk: &Hunk, position: usize, total: usize) {
+    println!(
+        "\n{} {} {}",
+        format!("[{position}/{total}]").bright_blue(),
+        hunk.path.display().to_string().bold(),
+        hunk.header.dimmed()
+    );
+    for line in hunk.patch.lines() {
+        if line.starts_with('+') {
+            println!("{}", line.green());
+        } else if line.starts_with('-') {
+            println!("{}", line.red());
+        } else {
+            println!("{line}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn rest_of_file_choices_settle_later_hunks_without_asking() -> Result<()> {
+        let paths: Vec<&Path> = ["a.rs", "a.rs", "a.rs", "b.rs", "b.rs", "c.rs", "d.rs"]
+            .into_iter()
+            .map(Path::new)
+            .collect();
+        let mut script = vec![
+            Choice::Skip,
+            Choice::StageRestOfFile,
+            Choice::SkipRestOfFile,
+            Choice::Stage,
+            Choice::Finish,
+        ]
+        .into_iter();
+        let mut asked = Vec::new();
+        let picked = walk_hunks(&paths, |index| {
+            asked.push(index);
+            Ok(script.next())
+        })?;
+
+        assert_eq!(asked, [0, 1, 3, 5, 6]);
+        assert_eq!(picked, [1, 2, 5]);
+        Ok(())
+    }
+}
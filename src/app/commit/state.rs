@@ -0,0 +1,121 @@
+use anyhow::Result;
+
+use super::{CommitAction, CommitCMD};
+use crate::error::Shelfor;
+use crate::git::{PendingOperation, operation_commits};
+
+/// Refuses what shelf can't do while `operation` is in progress, including
+/// committing an index that still has `conflicts`.
+pub(super) fn check(
+    operation: Option<PendingOperation>,
+    conflicts: bool,
+    args: &CommitCMD,
+) -> Result<()> {
+    let Some(operation) = operation else {
+        return Ok(());
+    };
+    let refusal = match &args.action {
+        _ if conflicts => Some("resolve the conflicts and stage the result first"),
+        Some(CommitAction::Reword { .. } | CommitAction::Squash { .. }) => {
+            Some("finish or abort it before rewriting history")
+        }
+        _ if args.split => Some("finish or abort it before splitting the changes"),
+        _ if args.amend && operation != PendingOperation::Rebase => {
+            Some("conclude it with a new commit instead of amending")
+        }
+        _ => None,
+    };
+    match refusal {
+        Some(hint) => Err(Shelfor::OperationInProgress(operation.name(), hint).into()),
+        None => Ok(()),
+    }
+}
+
+/// Tells the model what the commit concludes, with the commits the operation
+/// brings in.
+pub(super) fn context(operation: Option<PendingOperation>, limit: usize) -> Result<String> {
+    let Some(operation) = operation else {
+        return Ok(String::new());
+    };
+    let commits = operation_commits(operation, limit)?;
+    if operation == PendingOperation::Merge {
+        let listed: Vec<String> = commits
+            .iter()
+            .map(|(oid, message)| {
+                format!(
+                    "• {}: {}",
+                    &oid.to_string()[..7],
+                    message.lines().next().unwrap_or_default()
+                )
+            })
+            .collect();
+        return Ok(format!(
+            "This commit concludes a merge. Commits being merged:\n```\n{}\n```\n",
+            listed.join("\n")
+        ));
+    }
+
+    let Some((_, message)) = commits.first() else {
+        return Ok(String::new());
+    };
+    let intro = match operation {
+        PendingOperation::CherryPick { .. } => {
+            "This commit cherry-picks the commit below; keep its message unless the changes differ"
+        }
+        PendingOperation::Revert { .. } => {
+            "This commit reverts the commit below; say what is reverted and why"
+        }
+        _ => "This commit is made during a rebase replaying the commit below",
+    };
+    Ok(format!("{intro}:\n```\n{}\n```\n", message.trim_end()))
+}
+
+/// The command that carries on with `operation` once the commit is made.
+pub(super) fn continue_hint(operation: Option<PendingOperation>) -> Option<&'static str> {
+    match operation? {
+        PendingOperation::Rebase => Some("git rebase --continue"),
+        PendingOperation::CherryPick { sequence: true } => Some("git cherry-pick --continue"),
+        PendingOperation::Revert { sequence: true } => Some("git revert --continue"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        commit: CommitCMD,
+    }
+
+    fn refusal(operation: PendingOperation, conflicts: bool, args: &[&str]) -> Option<String> {
+        let cli = Cli::try_parse_from(std::iter::once("shelf").chain(args.iter().copied()))
+            .expect("valid arguments");
+        check(Some(operation), conflicts, &cli.commit)
+            .err()
+            .map(|error| error.to_string())
+    }
+
+    #[test]
+    fn operations_refuse_what_they_cannot_conclude() {
+        let merge = PendingOperation::Merge;
+        assert!(check(None, true, &Cli::parse_from(["shelf", "--split"]).commit).is_ok());
+        assert_eq!(refusal(merge, false, &[]), None);
+        assert_eq!(
+            refusal(merge, true, &[]).as_deref(),
+            Some(
+                "Cannot commit while a merge is in progress; resolve the conflicts and stage the result first"
+            )
+        );
+        assert!(refusal(merge, false, &["--split"]).is_some_and(|e| e.contains("splitting")));
+        assert!(refusal(merge, false, &["--amend"]).is_some_and(|e| e.contains("amending")));
+        assert_eq!(refusal(PendingOperation::Rebase, false, &["--amend"]), None);
+        assert!(
+            refusal(PendingOperation::Rebase, false, &["reword", "HEAD"])
+                .is_some_and(|e| e.contains("rewriting history"))
+        );
+    }
+}
@@ -0,0 +1,223 @@
+use anyhow::{Result, anyhow};
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use super::strip_code_fence;
+
+/// A commit message in parts, as the model is asked to return it.
+#[derive(Debug, PartialEq, Deserialize, JsonSchema)]
+pub(super) struct StructuredMessage {
+    /// Conventional Commits type such as `feat` or `fix`; empty when the
+    /// repository doesn't use Conventional Commits.
+    #[serde(default, rename = "type")]
+    kind: String,
+    /// Conventional Commits scope, such as the module the change touches.
+    #[serde(default)]
+    scope: Option<String>,
+    /// One-line summary in the imperative, without the type and scope.
+    subject: String,
+    /// What changed and why, wrapped at 80 characters; may be omitted.
+    #[serde(default)]
+    body: Option<String>,
+    /// Whether the change breaks compatibility, marked with `!` after the type.
+    #[serde(default)]
+    breaking: bool,
+    /// Footers such as `BREAKING CHANGE: …`, in order.
+    #[serde(default)]
+    footers: Vec<Footer>,
+}
+
+#[derive(Debug, PartialEq, Deserialize, JsonSchema)]
+pub(super) struct Footer {
+    /// Footer token, such as `BREAKING CHANGE` or `Refs`.
+    token: String,
+    value: String,
+}
+
+/// The JSON schema the model's reply must match.
+pub(super) fn schema() -> Result<String> {
+    Ok(serde_json::to_string_pretty(&schemars::schema_for!(
+        StructuredMessage
+    ))?)
+}
+
+/// Parses and checks the model's reply, failing with a reason the model can be told.
+pub(super) fn parse(response: &str) -> Result<StructuredMessage> {
+    let message: StructuredMessage = serde_json::from_str(strip_code_fence(response))
+        .map_err(|error| anyhow!("the reply is not JSON matching the schema ({error})"))?;
+    message.check()?;
+    Ok(message)
+}
+
+/// Checks that the assembled message keeps the partial message it was asked to
+/// complete; only its first line is compared, as the rest may be reworded.
+pub(super) fn check_prefix(message: &str, prefix: Option<&str>) -> Result<()> {
+    let start = prefix
+        .and_then(|prefix| prefix.trim().lines().next())
+        .unwrap_or_default();
+    if message.starts_with(start) {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "the assembled message does not start with the partial message `{start}`"
+        ))
+    }
+}
+
+/// Asks the model to fix a reply `parse` or `check_prefix` rejected.
+pub(super) fn repair_prompt(prompt: &str, reply: &str, error: &anyhow::Error) -> String {
+    format!(
+        "{prompt}\n\nYour previous reply was rejected because {error}:\n```\n{}\n```\n\
+         Reply again with JSON only, matching the schema above.",
+        reply.trim()
+    )
+}
+
+impl StructuredMessage {
+    fn check(&self) -> Result<()> {
+        let subject = self.subject.trim();
+        if subject.is_empty() {
+            return Err(anyhow!("the subject is empty"));
+        }
+        if subject.contains('\n') {
+            return Err(anyhow!("the subject spans several lines"));
+        }
+        if !self.kind.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(anyhow!("the type `{}` is not a single word", self.kind));
+        }
+        if let Some(scope) = self.scope() {
+            if self.kind.is_empty() {
+                return Err(anyhow!("a scope is given without a type"));
+            }
+            if scope.contains(['(', ')', ':', '\n']) {
+                return Err(anyhow!("the scope `{scope}` is not a plain name"));
+            }
+        }
+        for footer in &self.footers {
+            let token = footer.token.trim();
+            let word =
+                !token.is_empty() && token.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+            if !word && token != "BREAKING CHANGE" {
+                return Err(anyhow!("the footer token `{token}` is not a single word"));
+            }
+            if footer.value.trim().is_empty() {
+                return Err(anyhow!("the footer `{token}` has no value"));
+            }
+        }
+        Ok(())
+    }
+
+    fn scope(&self) -> Option<&str> {
+        self.scope
+            .as_deref()
+            .map(str::trim)
+            .filter(|scope| !scope.is_empty())
+    }
+
+    /// Builds the message: header, blank line, body, blank line, footers.
+    pub(super) fn assemble(&self) -> String {
+        let subject = self.subject.trim();
+        let mut message = if self.kind.is_empty() {
+            subject.to_string()
+        } else {
+            format!(
+                "{}{}{}: {subject}",
+                self.kind,
+                self.scope()
+                    .map(|scope| format!("({scope})"))
+                    .unwrap_or_default(),
+                if self.breaking { "!" } else { "" }
+            )
+        };
+
+        if let Some(body) = self.body.as_deref().map(str::trim)
+            && !body.is_empty()
+        {
+            message.push_str("\n\n");
+            message.push_str(body);
+        }
+        if !self.footers.is_empty() {
+            message.push_str("\n\n");
+            let footers: Vec<String> = self
+                .footers
+                .iter()
+                .map(|footer| format!("{}: {}", footer.token.trim(), footer.value.trim()))
+                .collect();
+            message.push_str(&footers.join("\n"));
+        }
+        message
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replies_are_assembled_into_messages() -> Result<()> {
+        let reply = r#"```json
+{
+  "type": "feat",
+  "scope": "cli",
+  "subject": "add --language ",
+  "body": "Lets teams write messages in their own language.\n",
+  "breaking": true,
+  "footers": [{"token": "BREAKING CHANGE", "value": "drops -l"}]
+}
+```"#;
+        assert_eq!(
+            parse(reply)?.assemble(),
+            "feat(cli)!: add --language\n\nLets teams write messages in their own language.\n\nBREAKING CHANGE: drops -l"
+        );
+        assert_eq!(
+            parse(r#"{"subject": "Fix the flaky test", "scope": ""}"#)?.assemble(),
+            "Fix the flaky test"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_replies_are_rejected() {
+        for reply in [
+            "Here is your commit message: fix: typo",
+            r#"{"type": "fix"}"#,
+            r#"{"type": "fix", "subject": " "}"#,
+            r#"{"type": "fix docs", "subject": "typo"}"#,
+            r#"{"scope": "cli", "subject": "typo"}"#,
+            r#"{"subject": "typo", "footers": [{"token": "See also", "value": "x"}]}"#,
+        ] {
+            assert!(parse(reply).is_err(), "{reply}");
+        }
+    }
+
+    #[test]
+    fn messages_must_keep_the_partial_message() -> Result<()> {
+        let message = parse(r#"{"type": "fix", "scope": "cli", "subject": "typo"}"#)?.assemble();
+        check_prefix(&message, None)?;
+        check_prefix(&message, Some(""))?;
+        check_prefix(&message, Some("fix(cli): \n\nRefs: PROJ-1"))?;
+        assert!(check_prefix(&message, Some("[PROJ-1] fix")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn preamble_examples_keep_their_partial_messages() -> Result<()> {
+        let preamble = std::fs::read_to_string(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/assets/prompts/commit_preamble.hbs"
+        ))?;
+        let mut lines = preamble.lines();
+        let mut examples = 0;
+        while let Some(line) = lines.next() {
+            let Some(partial) = line.strip_prefix("PARTIAL_COMMIT_MESSAGE: ") else {
+                continue;
+            };
+            assert_eq!(lines.next(), Some("COMPLETED_COMMIT_MESSAGE:"));
+            let reply = lines.next().unwrap_or_default();
+            check_prefix(&parse(reply)?.assemble(), Some(partial))?;
+            examples += 1;
+        }
+        assert!(examples > 0);
+        Ok(())
+    }
+}
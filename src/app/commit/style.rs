@@ -0,0 +1,391 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use directories::BaseDirs;
+use git2::Oid;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use super::{conventional, trailers};
+use crate::git::{commits_ahead, head_id, history_messages, repository_dir};
+
+/// Enough history to see a repository's habits without walking huge ones for long.
+const MAX_ANALYSED_COMMITS: usize = 2000;
+/// New commits since the analysis after which it's redone; a few more barely
+/// change the shares.
+const REANALYSE_AFTER_COMMITS: usize = 50;
+/// Below this many commits there is no style worth learning.
+const MIN_ANALYSED_COMMITS: usize = 5;
+/// A habit is stated when at least this share of commits follows it, and its
+/// absence when at most `1 - MAJORITY` do.
+const MAJORITY: f64 = 0.7;
+const TOP_ENTRIES: usize = 6;
+
+/// Habits mined from the repository's commit messages.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub(super) struct StyleGuide {
+    /// HEAD when the history was analysed, used to tell when the cache is stale.
+    head: Option<String>,
+    commits: usize,
+    /// Shares of commits, between 0 and 1.
+    conventional: f64,
+    bracket_prefix: f64,
+    capitalised: f64,
+    imperative: f64,
+    trailing_period: f64,
+    with_body: f64,
+    emoji: f64,
+    subject_median: usize,
+    subject_p90: usize,
+    /// Most used types, scopes and trailer tokens with their counts.
+    types: Vec<(String, usize)>,
+    scopes: Vec<(String, usize)>,
+    trailers: Vec<(String, usize)>,
+}
+
+/// Analyses `messages`, newest first.
+pub(super) fn analyse(messages: &[String]) -> StyleGuide {
+    let mut guide = StyleGuide {
+        commits: messages.len(),
+        ..StyleGuide::default()
+    };
+    if messages.is_empty() {
+        return guide;
+    }
+
+    let mut types = HashMap::new();
+    let mut scopes = HashMap::new();
+    let mut trailer_tokens = HashMap::new();
+    let mut subject_lengths = Vec::with_capacity(messages.len());
+    let (mut conventional_count, mut bracketed, mut capitalised, mut period) = (0, 0, 0, 0);
+    let (mut imperative, mut worded, mut with_body, mut emoji) = (0, 0, 0, 0);
+
+    for message in messages {
+        let subject = message.lines().next().unwrap_or_default().trim();
+        subject_lengths.push(subject.chars().count());
+
+        let description = match conventional::parse(message) {
+            Some(parsed) => {
+                conventional_count += 1;
+                *types.entry(parsed.kind.to_string()).or_insert(0) += 1;
+                if let Some(scope) = parsed.scope {
+                    *scopes.entry(scope.to_string()).or_insert(0) += 1;
+                }
+                parsed.subject.trim()
+            }
+            None if subject.starts_with('[') => {
+                bracketed += 1;
+                subject
+                    .split_once(']')
+                    .map_or(subject, |(_, rest)| rest.trim())
+            }
+            None => subject,
+        };
+        let description = strip_emoji(description);
+        if has_emoji(subject) || strip_emoji(subject) != subject {
+            emoji += 1;
+        }
+        if description.chars().next().is_some_and(char::is_uppercase) {
+            capitalised += 1;
+        }
+        if subject.ends_with('.') {
+            period += 1;
+        }
+        if let Some(word) = description.split_whitespace().next() {
+            worded += 1;
+            if is_imperative(word) {
+                imperative += 1;
+            }
+        }
+
+        let body = message
+            .lines()
+            .skip(1)
+            .filter(|line| !line.trim().is_empty());
+        let tokens = trailers::tokens(message);
+        if body.count() > tokens.len() {
+            with_body += 1;
+        }
+        for token in tokens {
+            *trailer_tokens.entry(token).or_insert(0) += 1;
+        }
+    }
+
+    let total = messages.len() as f64;
+    subject_lengths.sort_unstable();
+    guide.conventional = f64::from(conventional_count) / total;
+    guide.bracket_prefix = f64::from(bracketed) / total;
+    guide.capitalised = f64::from(capitalised) / total;
+    guide.trailing_period = f64::from(period) / total;
+    guide.with_body = f64::from(with_body) / total;
+    guide.emoji = f64::from(emoji) / total;
+    guide.imperative = if worded == 0 {
+        0.0
+    } else {
+        f64::from(imperative) / f64::from(worded)
+    };
+    guide.subject_median = subject_lengths[subject_lengths.len() / 2];
+    guide.subject_p90 =
+        subject_lengths[(subject_lengths.len() * 9 / 10).min(subject_lengths.len() - 1)];
+    guide.types = top(types);
+    guide.scopes = top(scopes);
+    guide.trailers = top(trailer_tokens);
+    guide
+}
+
+impl StyleGuide {
+    /// Compact instructions for the prompt, stating only clear habits. Prefix
+    /// habits are left out when `[commit.conventional]` already sets the rules.
+    pub(super) fn render(&self, conventional_configured: bool) -> String {
+        if self.commits < MIN_ANALYSED_COMMITS {
+            return String::new();
+        }
+        let percent = |share: f64| format!("{:.0}%", share * 100.0);
+        let listed = |entries: &[(String, usize)]| {
+            entries
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let mut lines = Vec::new();
+
+        if conventional_configured {
+            // The configured rules are stated instead, and may differ.
+        } else if self.conventional >= MAJORITY {
+            lines.push(format!(
+                "Use Conventional Commits headers ({} of commits); common types: {}",
+                percent(self.conventional),
+                listed(&self.types)
+            ));
+            if !self.scopes.is_empty() {
+                lines.push(format!("Common scopes: {}", listed(&self.scopes)));
+            }
+        } else if self.bracket_prefix >= MAJORITY {
+            lines.push(format!(
+                "Start subjects with a bracketed prefix such as `[ID]` ({} of commits)",
+                percent(self.bracket_prefix)
+            ));
+        } else if self.conventional <= 1.0 - MAJORITY {
+            lines.push("Do not use Conventional Commits prefixes".to_string());
+        }
+
+        if self.capitalised >= MAJORITY {
+            lines.push("Start the subject description with a capital letter".to_string());
+        } else if self.capitalised <= 1.0 - MAJORITY {
+            lines.push("Start the subject description with a lowercase letter".to_string());
+        }
+        if self.imperative >= MAJORITY {
+            lines.push(
+                "Write the subject in the imperative mood (\"add\", not \"added\")".to_string(),
+            );
+        } else if self.imperative <= 1.0 - MAJORITY {
+            lines.push(
+                "Write the subject in the past tense or third person, as past commits do"
+                    .to_string(),
+            );
+        }
+        lines.push(format!(
+            "Subjects are typically {} characters long and rarely over {}",
+            self.subject_median, self.subject_p90
+        ));
+        if self.trailing_period <= 1.0 - MAJORITY {
+            lines.push("Do not end the subject with a period".to_string());
+        } else if self.trailing_period >= MAJORITY {
+            lines.push("End the subject with a period".to_string());
+        }
+        if self.with_body >= MAJORITY {
+            lines.push("Add a body explaining the change".to_string());
+        } else if self.with_body <= 1.0 - MAJORITY {
+            lines.push("Usually write the subject line only, without a body".to_string());
+        }
+        if self.emoji >= MAJORITY {
+            lines.push(format!(
+                "Start with an emoji ({} of commits)",
+                percent(self.emoji)
+            ));
+        } else if self.emoji <= 1.0 - MAJORITY {
+            lines.push("Do not use emoji".to_string());
+        }
+        if let Some((token, count)) = self.trailers.first()
+            && *count as f64 / self.commits as f64 >= MAJORITY
+        {
+            lines.push(format!("Commits usually carry a `{token}:` trailer"));
+        }
+
+        let lines: String = lines.iter().map(|line| format!("- {line}\n")).collect();
+        format!(
+            "COMMIT_STYLE (learned from the last {} commits of this repository; follow it):\n{lines}",
+            self.commits
+        )
+    }
+}
+
+/// Loads the style guide from the cache, analysing the history again when
+/// `refresh` is set, HEAD no longer descends from the analysed commit, or
+/// many commits were made since. Cache failures are not fatal.
+pub(super) fn load(refresh: bool) -> Result<StyleGuide> {
+    let cache = cache_path().ok();
+    if !refresh
+        && let Some(cached) = cache
+            .as_ref()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str::<StyleGuide>(&content).ok())
+        && is_current(&cached, head_id()?)?
+    {
+        return Ok(cached);
+    }
+
+    let (head, messages) = history_messages(MAX_ANALYSED_COMMITS)?;
+    let head = head.map(|oid| oid.to_string());
+    let guide = StyleGuide {
+        head,
+        ..analyse(&messages)
+    };
+    if let Some(path) = cache
+        && let Some(parent) = path.parent()
+        && std::fs::create_dir_all(parent).is_ok()
+        && let Ok(json) = serde_json::to_string_pretty(&guide)
+    {
+        let _ = std::fs::write(path, json);
+    }
+    Ok(guide)
+}
+
+/// Whether a cached guide still describes the history up to `head`.
+fn is_current(cached: &StyleGuide, head: Option<Oid>) -> Result<bool> {
+    match (cached.head.as_deref().map(Oid::from_str), head) {
+        (None, None) => Ok(true),
+        (Some(Ok(analysed)), Some(head)) => Ok(
+            commits_ahead(analysed, head)?.is_some_and(|ahead| ahead <= REANALYSE_AFTER_COMMITS)
+        ),
+        _ => Ok(false),
+    }
+}
+
+/// Prints the style guide for `shelf commit style`.
+pub(super) fn show(refresh: bool, conventional_configured: bool) -> Result<()> {
+    let guide = load(refresh)?;
+    let rendered = guide.render(conventional_configured);
+    if rendered.is_empty() {
+        println!(
+            "{}",
+            format!(
+                "Only {} commits in the history, too few to learn a style from.",
+                guide.commits
+            )
+            .bright_blue()
+        );
+    } else {
+        print!("{rendered}");
+    }
+    if let Ok(path) = cache_path() {
+        println!("\n{} {}", "Cached at".dimmed(), path.display());
+    }
+    Ok(())
+}
+
+/// `<cache dir>/shelf/style/<repository>.json`, named after the git directory.
+fn cache_path() -> Result<PathBuf> {
+    let base_dirs = BaseDirs::new().context("Could not find the cache directory")?;
+    let repository = repository_dir()?;
+    let name: String = repository
+        .to_string_lossy()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    Ok(base_dirs
+        .cache_dir()
+        .join("shelf")
+        .join("style")
+        .join(format!("{}.json", name.trim_matches('_'))))
+}
+
+fn top(counts: HashMap<String, usize>) -> Vec<(String, usize)> {
+    let mut entries: Vec<(String, usize)> = counts.into_iter().collect();
+    entries.sort_by(|(a_name, a), (b_name, b)| b.cmp(a).then_with(|| a_name.cmp(b_name)));
+    entries.truncate(TOP_ENTRIES);
+    entries
+}
+
+/// Guesses the mood from the first word: "added" and "adds" are not imperative.
+fn is_imperative(word: &str) -> bool {
+    let word = word.to_lowercase();
+    let past = word.ends_with("ed")
+        && !matches!(word.as_str(), "need" | "embed" | "seed" | "feed" | "shed");
+    let third_person = word.ends_with('s')
+        && !word.ends_with("ss")
+        && !word.ends_with("us")
+        && !word.ends_with("is")
+        && word.len() > 3;
+    !past && !third_person
+}
+
+fn has_emoji(text: &str) -> bool {
+    text.chars()
+        .any(|c| matches!(u32::from(c), 0x1F300..=0x1FAFF | 0x2600..=0x27BF))
+}
+
+/// Drops a leading emoji or `:gitmoji:` shortcode.
+fn strip_emoji(text: &str) -> &str {
+    let text = text.trim_start();
+    if let Some(rest) = text.strip_prefix(':')
+        && let Some((code, rest)) = rest.split_once(':')
+        && !code.is_empty()
+        && code
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return rest.trim_start();
+    }
+    let end = text
+        .char_indices()
+        .find(|(_, c)| !has_emoji(&c.to_string()) && *c != '\u{FE0F}')
+        .map_or(text.len(), |(index, _)| index);
+    text[end..].trim_start()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(subjects: &[&str]) -> Vec<String> {
+        subjects.iter().map(|subject| subject.to_string()).collect()
+    }
+
+    #[test]
+    fn conventional_lowercase_imperative_history_is_recognised() {
+        let guide = analyse(&messages(&[
+            "feat(cli): add changelog command",
+            "fix: handle empty ranges\n\nThe walk failed on unborn branches.\n\nRefs: PROJ-1",
+            "fix(dots): keep file modes",
+            "docs: explain ignore patterns",
+            "feat: support ssh signing",
+            "Merged things.",
+        ]));
+        assert_eq!(guide.commits, 6);
+        assert!((guide.conventional - 5.0 / 6.0).abs() < f64::EPSILON);
+        assert_eq!(guide.types[0], ("feat".to_string(), 2));
+        assert_eq!(guide.trailers, vec![("Refs".to_string(), 1)]);
+
+        let rendered = guide.render(false);
+        assert!(rendered.contains("Use Conventional Commits headers (83% of commits)"));
+        assert!(rendered.contains("lowercase letter"));
+        assert!(rendered.contains("imperative mood"));
+        assert!(rendered.contains("subject line only"));
+        assert!(!rendered.contains("trailer"));
+        assert!(!guide.render(true).contains("Conventional Commits"));
+    }
+
+    #[test]
+    fn emoji_and_mood_heuristics() {
+        assert!(is_imperative("add"));
+        assert!(is_imperative("Address"));
+        assert!(!is_imperative("added"));
+        assert!(!is_imperative("fixes"));
+        assert_eq!(strip_emoji(":sparkles: Add x"), "Add x");
+        assert_eq!(strip_emoji("✨ Add x"), "Add x");
+        assert_eq!(strip_emoji("Add x"), "Add x");
+        assert!(analyse(&messages(&["Add x"])).render(false).is_empty());
+    }
+}
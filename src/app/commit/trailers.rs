@@ -0,0 +1,257 @@
+use anyhow::{Context, Result, anyhow};
+use regex::Regex;
+use std::fmt;
+
+use crate::config::{CommitSettings, TicketConfig};
+
+/// A `Token: value` line in the trailer block at the end of a message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Trailer {
+    pub(super) token: String,
+    pub(super) value: String,
+}
+
+impl Trailer {
+    /// Parses `Token: value` or `Token=value`, the forms `git commit --trailer` accepts.
+    pub(super) fn parse(text: &str) -> Result<Self> {
+        let (token, value) = text
+            .split_once(':')
+            .or_else(|| text.split_once('='))
+            .ok_or_else(|| anyhow!("Trailer {text:?} is not in the form `Token: value`"))?;
+        let (token, value) = (token.trim(), value.trim());
+        if !is_token(token) || value.is_empty() {
+            return Err(anyhow!(
+                "Trailer {text:?} is not in the form `Token: value`"
+            ));
+        }
+        Ok(Self {
+            token: token.to_string(),
+            value: value.to_string(),
+        })
+    }
+
+    fn matches(&self, token: &str, value: &str) -> bool {
+        self.token.eq_ignore_ascii_case(token.trim()) && self.value == value.trim()
+    }
+}
+
+impl fmt::Display for Trailer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.token, self.value)
+    }
+}
+
+/// Extracts ticket IDs from `branch` with the configured patterns, in order and without repeats.
+pub(super) fn ticket_ids(branch: &str, config: &TicketConfig) -> Result<Vec<String>> {
+    let mut ids: Vec<String> = Vec::new();
+    for pattern in &config.patterns {
+        let regex =
+            Regex::new(pattern).with_context(|| format!("Invalid ticket pattern {pattern:?}"))?;
+        for captures in regex.captures_iter(branch) {
+            let id = captures.get(1).or_else(|| captures.get(0));
+            if let Some(id) = id.map(|id| id.as_str().to_string())
+                && !ids.contains(&id)
+            {
+                ids.push(id);
+            }
+        }
+    }
+    Ok(ids)
+}
+
+/// Gathers the trailers to append: one per ticket, then the configured ones,
+/// then those given on the command line.
+pub(super) fn collect(
+    settings: &CommitSettings,
+    tickets: &[String],
+    from_args: &[String],
+) -> Result<Vec<Trailer>> {
+    let ticket_token = settings
+        .tickets
+        .as_ref()
+        .map(|tickets| tickets.trailer.as_str())
+        .unwrap_or("Refs");
+    let mut trailers: Vec<Trailer> = tickets
+        .iter()
+        .map(|id| Trailer {
+            token: ticket_token.to_string(),
+            value: id.clone(),
+        })
+        .collect();
+    trailers.extend(settings.trailers.iter().map(|trailer| Trailer {
+        token: trailer.token.clone(),
+        value: trailer.value.clone(),
+    }));
+    for text in from_args {
+        trailers.push(Trailer::parse(text)?);
+    }
+    Ok(trailers)
+}
+
+/// Appends `trailers` the way `git interpret-trailers` does: to the existing
+/// trailer block if the message ends with one, otherwise as a new paragraph.
+/// Trailers already present with the same token and value are not repeated.
+pub(super) fn append<'t>(message: &str, trailers: impl IntoIterator<Item = &'t Trailer>) -> String {
+    let mut lines: Vec<String> = message.trim_end().lines().map(str::to_string).collect();
+    let block_start = trailer_block_start(&lines);
+    let existing: Vec<(String, String)> = block_start
+        .map(|start| {
+            lines[start..]
+                .iter()
+                .filter_map(|line| split_trailer(line))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let missing: Vec<&Trailer> = trailers
+        .into_iter()
+        .filter(|trailer| {
+            !existing
+                .iter()
+                .any(|(token, value)| trailer.matches(token, value))
+        })
+        .collect();
+    if missing.is_empty() {
+        return message.to_string();
+    }
+
+    if block_start.is_none() && !lines.is_empty() {
+        lines.push(String::new());
+    }
+    lines.extend(missing.iter().map(|trailer| trailer.to_string()));
+    format!("{}\n", lines.join("\n"))
+}
+
+/// Whether the message's trailer block already holds `trailer`.
+pub(super) fn contains(message: &str, trailer: &Trailer) -> bool {
+    let lines: Vec<String> = message.trim_end().lines().map(str::to_string).collect();
+    trailer_block_start(&lines).is_some_and(|start| {
+        lines[start..]
+            .iter()
+            .filter_map(|line| split_trailer(line))
+            .any(|(token, value)| trailer.matches(&token, &value))
+    })
+}
+
+/// Tokens of the message's trailer block, in order.
+pub(super) fn tokens(message: &str) -> Vec<String> {
+    let lines: Vec<String> = message.trim_end().lines().map(str::to_string).collect();
+    trailer_block_start(&lines)
+        .map(|start| {
+            lines[start..]
+                .iter()
+                .filter_map(|line| split_trailer(line))
+                .map(|(token, _)| token)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Removes `trailers` from the message's trailer block, dropping the block if it empties.
+pub(super) fn strip(message: &str, trailers: &[Trailer]) -> String {
+    let lines: Vec<String> = message.trim_end().lines().map(str::to_string).collect();
+    let Some(start) = trailer_block_start(&lines) else {
+        return message.to_string();
+    };
+
+    let kept: Vec<&String> = lines[start..]
+        .iter()
+        .filter(|line| {
+            !split_trailer(line).is_some_and(|(token, value)| {
+                trailers
+                    .iter()
+                    .any(|trailer| trailer.matches(&token, &value))
+            })
+        })
+        .collect();
+    let mut result: Vec<&String> = lines[..start].iter().collect();
+    if kept.is_empty() {
+        while result.last().is_some_and(|line| line.trim().is_empty()) {
+            result.pop();
+        }
+    } else {
+        result.extend(kept);
+    }
+    let result: Vec<&str> = result.iter().map(|line| line.as_str()).collect();
+    format!("{}\n", result.join("\n"))
+}
+
+/// Index of the first line of the trailer block: the last paragraph, when it
+/// isn't the subject and every line is a trailer or a continuation of one.
+fn trailer_block_start(lines: &[String]) -> Option<usize> {
+    let start = lines
+        .iter()
+        .rposition(|line| line.trim().is_empty())
+        .map(|blank| blank + 1)?;
+    let block = &lines[start..];
+    let is_block = !block.is_empty()
+        && split_trailer(&block[0]).is_some()
+        && block.iter().all(|line| {
+            split_trailer(line).is_some() || line.starts_with(' ') || line.starts_with('\t')
+        });
+    is_block.then_some(start)
+}
+
+fn split_trailer(line: &str) -> Option<(String, String)> {
+    let (token, value) = line.split_once(':')?;
+    is_token(token.trim_end()).then(|| (token.trim().to_string(), value.trim().to_string()))
+}
+
+/// Trailer tokens are words joined by hyphens, with no spaces.
+fn is_token(token: &str) -> bool {
+    !token.is_empty() && token.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trailer(text: &str) -> Trailer {
+        Trailer::parse(text).expect("valid trailer")
+    }
+
+    #[test]
+    fn ticket_ids_come_from_the_branch_name() -> Result<()> {
+        let config = TicketConfig {
+            patterns: vec![
+                r"\b([A-Z][A-Z0-9]+-[0-9]+)\b".to_string(),
+                r"#[0-9]+".to_string(),
+            ],
+            trailer: "Refs".to_string(),
+        };
+        assert_eq!(
+            ticket_ids("feat/PROJ-1234-some-thing-PROJ-1234-OPS-7-#42", &config)?,
+            vec!["PROJ-1234", "OPS-7", "#42"]
+        );
+        assert!(ticket_ids("main", &config)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn trailers_join_an_existing_block_without_repeats() {
+        let refs = trailer("Refs: PROJ-1");
+        let co_author = trailer("Co-authored-by: Jane <jane@example.com>");
+
+        assert_eq!(
+            append("feat: add x\n\nBody text.", [&refs, &co_author]),
+            "feat: add x\n\nBody text.\n\nRefs: PROJ-1\nCo-authored-by: Jane <jane@example.com>\n"
+        );
+        assert_eq!(
+            append("feat: add x\n\nrefs: PROJ-1\n", [&refs, &co_author]),
+            "feat: add x\n\nrefs: PROJ-1\nCo-authored-by: Jane <jane@example.com>\n"
+        );
+        // A subject that looks like a trailer is not a trailer block.
+        assert_eq!(
+            append("fix: crash", [&refs]),
+            "fix: crash\n\nRefs: PROJ-1\n"
+        );
+
+        let appended = append("feat: add x\n\nBody text.", [&refs, &co_author]);
+        assert!(contains(&appended, &refs));
+        assert_eq!(
+            strip(&appended, &[refs, co_author]),
+            "feat: add x\n\nBody text.\n"
+        );
+        assert!(Trailer::parse("not a trailer").is_err());
+    }
+}
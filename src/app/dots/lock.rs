@@ -0,0 +1,105 @@
+use anyhow::Result;
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+use std::{process, thread};
+
+use crate::error::Shelfor;
+
+const LOCK_FILE_NAME: &str = "shelf.lock";
+const LOCK_POLL_MS: u64 = 200;
+
+/// Advisory lock held for the duration of a mutating vault operation.
+///
+/// The lock is an exclusive OS file lock on a file inside the vault's git
+/// directory, which also records the owner's PID for error messages. The OS
+/// releases it when the guard is dropped or the owner dies, so a crashed
+/// process never leaves a lock behind for others to reclaim.
+#[derive(Debug)]
+pub struct VaultLock {
+    _file: File,
+}
+
+impl VaultLock {
+    /// Acquires the lock for the vault at `git_dir`.
+    ///
+    /// When another live process holds the lock, either fails with
+    /// [`Shelfor::VaultLocked`] or, if `wait` is set, blocks until it is released.
+    pub fn acquire(git_dir: &Path, wait: bool) -> Result<Self> {
+        let path = git_dir.join(LOCK_FILE_NAME);
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        let mut announced = false;
+
+        loop {
+            match file.try_lock() {
+                Ok(()) => {
+                    file.set_len(0)?;
+                    writeln!(file, "{}", process::id())?;
+                    return Ok(Self { _file: file });
+                }
+                Err(TryLockError::WouldBlock) => {}
+                Err(TryLockError::Error(e)) => return Err(e.into()),
+            }
+
+            let pid = holder_pid(&path).unwrap_or_default();
+            if !wait {
+                return Err(Shelfor::VaultLocked(pid, path).into());
+            }
+            if !announced {
+                eprintln!("Waiting for vault lock held by process {pid}...");
+                announced = true;
+            }
+            thread::sleep(Duration::from_millis(LOCK_POLL_MS));
+        }
+    }
+}
+
+/// Reads the PID recorded in the lock file, if any.
+fn holder_pid(path: &Path) -> Option<u32> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn lock_is_exclusive_and_released_on_drop() -> Result<()> {
+        let dir = tempdir()?;
+        let lock = VaultLock::acquire(dir.path(), false)?;
+
+        let err = VaultLock::acquire(dir.path(), false).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Shelfor>(),
+            Some(Shelfor::VaultLocked(pid, _)) if *pid == process::id()
+        ));
+
+        drop(lock);
+        VaultLock::acquire(dir.path(), false)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn lock_left_by_a_dead_process_is_free() -> Result<()> {
+        let dir = tempdir()?;
+        // A crashed owner leaves its PID behind but no OS lock; PIDs never
+        // get this large on any supported platform.
+        fs::write(dir.path().join(LOCK_FILE_NAME), "4294967295\n")?;
+
+        let _lock = VaultLock::acquire(dir.path(), false)?;
+        assert_eq!(
+            holder_pid(&dir.path().join(LOCK_FILE_NAME)),
+            Some(process::id())
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,2659 @@
+use anyhow::Result;
+use anyhow::anyhow;
+use clap::{Args, Subcommand};
+use colored::Colorize;
+use dialoguer::{Confirm, Select, theme::ColorfulTheme};
+use git2::build::CheckoutBuilder;
+use git2::{Index, Repository, RepositoryState, Statuses};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::{borrow::Cow, collections, fs};
+use tracing::debug;
+use walkdir::WalkDir;
+
+mod lock;
+mod roots;
+
+use self::lock::VaultLock;
+use self::roots::{FilePermissions, find_root, from_vault_path, vault_path};
+use super::commit::{determine_editor, launch_editor};
+use crate::config::{DotsConfig, RootConfig, find_and_load_config};
+use crate::git::verify_git_installation;
+use crate::{error::Shelfor, utils::shine_success};
+
+const HEADER_DIRECTORY: &str = "DIRECTORY";
+const HEADER_ITEM: &str = "ITEM";
+const HEADER_TYPE: &str = "TYPE";
+const EMPTY_DIR_DOT: &str = ".";
+const TYPE_DIR: &str = "Dir";
+const TYPE_FILE: &str = "File";
+const SAVE_SUCCESS: &str = "DotFs saved successfully";
+const MERGE_SUCCESS: &str = "Vault merged successfully";
+const UP_TO_DATE: &str = "Vault already up to date";
+const CONFLICT_MARKER: &str = "<<<<<<<";
+/// Bits of an index entry's flags holding the merge stage.
+const INDEX_STAGE_MASK: u16 = 0x3000;
+const HEADER_SIZE: &str = "SIZE";
+const HEADER_PATH: &str = "PATH";
+
+#[derive(Args)]
+pub struct DotsCMD {
+    #[command(subcommand)]
+    action: FileAction,
+    /// Wait for another shelf process to release the vault instead of failing.
+    #[arg(long, global = true)]
+    wait: bool,
+    /// Show what would change without touching the vault.
+    #[arg(long, global = true)]
+    dry_run: bool,
+}
+
+#[derive(Subcommand)]
+pub enum FileAction {
+    /// Track files for management.
+    Track {
+        /// Paths to the files to track.
+        paths: Vec<PathBuf>,
+    },
+    /// Remove files from management.
+    Untrack {
+        /// Paths to the files to untrack.
+        paths: Vec<PathBuf>,
+    },
+    /// List all currently tracked files.
+    List {
+        /// List only modified files.
+        #[arg(short, long)]
+        dirty: bool,
+    },
+    /// Save files for management.
+    Save,
+    /// Fetch the vault from a remote and merge it into the local history.
+    Pull {
+        /// Remote to pull from.
+        #[arg(default_value = "origin")]
+        remote: String,
+        /// Branch to merge, defaults to the vault's current branch.
+        branch: Option<String>,
+    },
+    /// Resolve conflicts left by a pull and conclude the merge.
+    Resolve,
+    /// Write files tracked outside the home directory back to their roots.
+    Restore {
+        /// Restore only these paths, defaults to every file under a root.
+        paths: Vec<PathBuf>,
+    },
+    /// Show the largest tracked files and historical blobs in the vault.
+    Du {
+        /// Number of entries to show in each section.
+        #[arg(short = 'n', long, default_value = "10")]
+        limit: usize,
+    },
+    /// Rewrite vault history to drop a path entirely, then repack.
+    Purge {
+        /// Path to remove from every commit.
+        path: PathBuf,
+        /// Skip the confirmation prompt.
+        #[arg(short, long)]
+        yes: bool,
+    },
+}
+
+pub async fn run(args: DotsCMD, mut repo: Dots) -> Result<()> {
+    repo.configure(find_and_load_config()?.dots);
+    repo.set_wait_for_lock(args.wait);
+    repo.set_dry_run(args.dry_run);
+
+    match args.action {
+        FileAction::Track { paths } => {
+            let changes = repo.track(&paths)?;
+            if args.dry_run {
+                print_index_changes(&changes);
+                return Ok(());
+            }
+            for path in paths {
+                println!("Tracking {}", path.display().to_string().bright_green());
+            }
+        }
+        FileAction::Untrack { paths } => {
+            let changes = repo.untrack(&paths)?;
+            if args.dry_run {
+                print_index_changes(&changes);
+                return Ok(());
+            }
+            for path in paths {
+                println!("Untracking {}", path.display().to_string().bright_red());
+            }
+        }
+        FileAction::List { dirty } => {
+            if dirty {
+                repo.set_filter(ListFilter::Modified);
+            }
+            let paths = repo.collect::<Vec<_>>();
+            let paths_by_dir = group_tabs_by_directory(paths);
+            print_grouped_paths(&paths_by_dir);
+        }
+        FileAction::Save => {
+            let message = repo.save_local_changes()?;
+            if args.dry_run {
+                print_commit_preview(&repo, &message)?;
+                return Ok(());
+            }
+            println!("{}", SAVE_SUCCESS.bright_green());
+        }
+        FileAction::Pull { remote, branch } => match repo.pull(&remote, branch.as_deref())? {
+            MergeOutcome::UpToDate => println!("{}", UP_TO_DATE.bright_green()),
+            MergeOutcome::FastForward | MergeOutcome::Merged => {
+                shine_success(MERGE_SUCCESS);
+                if repo.has_roots() {
+                    println!(
+                        "Run `shelf dots restore` to apply changes outside the home directory."
+                    );
+                }
+            }
+            MergeOutcome::Conflicts(paths) => {
+                println!("{}", "Merge stopped with conflicts in:".bright_yellow());
+                for path in paths {
+                    println!("  {}", path.display().to_string().bright_red());
+                }
+                println!("Run `shelf dots resolve` to settle them.");
+            }
+        },
+        FileAction::Resolve => resolve_conflicts(&repo)?,
+        FileAction::Restore { paths } => {
+            let verb = if args.dry_run {
+                "Would restore"
+            } else {
+                "Restored"
+            };
+            for path in repo.restore_roots(&paths)? {
+                println!("{verb} {}", path.display().to_string().bright_green());
+            }
+        }
+        FileAction::Du { limit } => {
+            let usage = repo.disk_usage(limit)?;
+            print_disk_usage(&usage);
+        }
+        FileAction::Purge { path, yes } => {
+            let confirmed = yes
+                || args.dry_run
+                || Confirm::with_theme(&ColorfulTheme::default())
+                    .with_prompt(format!(
+                        "Rewrite vault history to drop {}? This cannot be undone",
+                        path.display()
+                    ))
+                    .default(false)
+                    .interact()?;
+            if !confirmed {
+                println!("{}", "Purge cancelled.".bright_blue());
+                return Ok(());
+            }
+
+            let rewritten = repo.purge(&path)?;
+            if args.dry_run {
+                println!(
+                    "Would purge {} from {rewritten} commit(s)",
+                    path.display().to_string().bright_yellow()
+                );
+                return Ok(());
+            }
+            shine_success(&format!(
+                "Purged {} from {rewritten} commit(s)",
+                path.display()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Walks every conflicted path asking whether to keep ours, take theirs or edit
+/// the markers by hand, then concludes the merge once nothing is left.
+fn resolve_conflicts(repo: &Dots) -> Result<()> {
+    if !repo.is_merging() {
+        return Err(anyhow!("No merge in progress"));
+    }
+
+    for relative in repo.conflicted_paths()? {
+        match prompt_resolution(repo, &relative)? {
+            Some(resolution) => repo.resolve_conflict(&relative, resolution)?,
+            None => println!("Skipping {}", relative.display().to_string().yellow()),
+        }
+    }
+
+    let remaining = repo.conflicted_paths()?;
+    if !remaining.is_empty() {
+        println!(
+            "{} conflict(s) remain, run `shelf dots resolve` again to finish the merge.",
+            remaining.len()
+        );
+        return Ok(());
+    }
+
+    repo.finish_merge()?;
+    shine_success(MERGE_SUCCESS);
+    Ok(())
+}
+
+/// Asks how a single conflicted path should be resolved. `None` means skip it for now.
+fn prompt_resolution(repo: &Dots, relative: &Path) -> Result<Option<Resolution>> {
+    let options = [
+        "Keep ours (local)",
+        "Take theirs (remote)",
+        "Edit with Editor",
+        "Skip",
+    ];
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!("Conflict in {}", relative.display()))
+        .default(0)
+        .items(&options)
+        .interact()?;
+
+    match selection {
+        0 => Ok(Some(Resolution::Ours)),
+        1 => Ok(Some(Resolution::Theirs)),
+        2 => {
+            launch_editor(&determine_editor(), &repo.workdir()?.join(relative))?;
+            Ok(Some(Resolution::Edited))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Prints the index entries a dry-run track or untrack would touch.
+fn print_index_changes(changes: &IndexChanges) {
+    let sections = [
+        ("Would add", &changes.added),
+        ("Would update", &changes.updated),
+        ("Would remove", &changes.removed),
+    ];
+    if sections.iter().all(|(_, paths)| paths.is_empty()) {
+        println!("{}", "Nothing would change.".bright_blue());
+        return;
+    }
+
+    for (title, paths) in sections {
+        if paths.is_empty() {
+            continue;
+        }
+        println!("\n{} ({})", title.bold(), paths.len());
+        print_grouped_paths(&group_tabs_by_directory(paths.clone()));
+    }
+}
+
+/// Prints the commit a dry-run save would create, followed by the staged paths.
+fn print_commit_preview(repo: &Dots, message: &str) -> Result<()> {
+    let parent = repo
+        .get_parent_commits()?
+        .first()
+        .map(|commit| commit.id().to_string()[..7].to_string())
+        .unwrap_or_else(|| "none".to_string());
+    println!(
+        "{} on {} (parent {parent}):",
+        "Would create commit".bold(),
+        repo.current_branch()?.bright_green()
+    );
+    for line in message.lines() {
+        println!("    {}", line.bright_yellow());
+    }
+    println!();
+    print_grouped_paths(&group_tabs_by_directory(repo.staged_paths()?));
+    Ok(())
+}
+
+/// Formats a byte count using binary units.
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} {}", UNITS[0])
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+fn print_disk_usage(usage: &DiskUsage) {
+    println!(
+        "{} {}",
+        "Vault size on disk:".bold(),
+        format_size(usage.on_disk).bright_yellow()
+    );
+
+    for (title, rows) in [
+        ("Largest tracked files", &usage.tracked),
+        ("Largest blobs in history", &usage.history),
+    ] {
+        println!("\n{}", title.bold());
+        println!("{:<10} {}", HEADER_SIZE.bold(), HEADER_PATH.bold());
+        println!("{:-<10} {:-<4}", "", "");
+        for (path, size) in rows {
+            let padded_size = format!("{: <10}", format_size(*size));
+            println!(
+                "{} {}",
+                padded_size.cyan(),
+                path.display().to_string().bright_green()
+            );
+        }
+    }
+}
+
+fn group_tabs_by_directory(paths: Vec<PathBuf>) -> collections::BTreeMap<PathBuf, Vec<PathBuf>> {
+    debug!("Grouping {} paths by directory", paths.len());
+    let mut paths_by_dir: collections::BTreeMap<PathBuf, Vec<PathBuf>> =
+        collections::BTreeMap::new();
+    for file in paths {
+        let parent = file.parent().unwrap_or_else(|| Path::new("")).to_path_buf();
+        paths_by_dir.entry(parent).or_default().push(file);
+    }
+
+    paths_by_dir
+}
+
+fn get_home_dir() -> PathBuf {
+    directories::UserDirs::new()
+        .map(|dirs| dirs.home_dir().to_path_buf())
+        .unwrap_or_else(|| PathBuf::from("/"))
+}
+
+fn display_path_relative_to_home<'a>(path: &'a Path, home: &'a Path) -> Cow<'a, Path> {
+    path.strip_prefix(home)
+        .map_or_else(|_| Cow::Borrowed(path), Cow::Borrowed)
+}
+
+fn print_grouped_paths(paths_by_dir: &collections::BTreeMap<PathBuf, Vec<PathBuf>>) {
+    let home = get_home_dir();
+
+    // Determine max widths for columns based on uncolored strings
+    let mut max_dir_len = HEADER_DIRECTORY.len();
+    let mut max_item_len = HEADER_ITEM.len();
+
+    // Collect all rows as plain strings first to calculate accurate column widths
+    let mut rows: Vec<(String, String, String)> = Vec::new();
+
+    for (dir, files) in paths_by_dir.iter() {
+        let display_dir_cow = display_path_relative_to_home(dir, &home);
+        let display_dir_str = if display_dir_cow.as_os_str().is_empty() {
+            EMPTY_DIR_DOT.to_string() // Represent empty path as '.' for display
+        } else {
+            display_dir_cow.display().to_string()
+        };
+        max_dir_len = max_dir_len.max(display_dir_str.len());
+
+        for file in files {
+            let item_name_cow = file.file_name().unwrap_or_default().to_string_lossy();
+            let item_name_str = item_name_cow.to_string();
+            max_item_len = max_item_len.max(item_name_str.len());
+
+            let item_type = if file.is_dir() { TYPE_DIR } else { TYPE_FILE };
+            rows.push((
+                display_dir_str.clone(),
+                item_name_str,
+                item_type.to_string(),
+            ));
+        }
+    }
+
+    // Print Headers
+    println!(
+        "{:<width_dir$} {:<width_item$} {:<4}",
+        HEADER_DIRECTORY.bold(),
+        HEADER_ITEM.bold(),
+        HEADER_TYPE.bold(),
+        width_dir = max_dir_len,
+        width_item = max_item_len,
+    );
+    println!(
+        "{:-<width_dir$} {:-<width_item$} {:-<4}",
+        "",
+        "",
+        "",
+        width_dir = max_dir_len,
+        width_item = max_item_len,
+    );
+
+    // Print Data Rows
+    for (dir_str, item_name, item_type) in rows {
+        // Pad the string first to the determined width, then apply color.
+        // This ensures padding is based on visual length, not byte length including ANSI escape codes.
+        let padded_dir = format!("{dir_str: <max_dir_len$}");
+        let padded_item = format!("{item_name: <max_item_len$}");
+        let padded_type = format!("{item_type: <4}");
+
+        println!(
+            "{} {} {}",
+            padded_dir.blue().bold(),
+            padded_item.bright_green(),
+            padded_type.cyan(),
+        );
+    }
+}
+
+/// Manages system configuration files using a bare Git repository in the user's home directory.
+pub struct Dots {
+    bare: Repository,
+    settings: DotsConfig,
+    wait_for_lock: bool,
+    dry_run: bool,
+    filter: ListFilter,
+    filtered_entries: Vec<PathBuf>, // Pre-collected entries for iteration
+    iter_index: usize,              // Tracks iteration progress
+}
+
+impl std::fmt::Debug for Dots {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Git directory (bare repo path)
+        let git_dir = self.bare.path().display();
+
+        // Workdir may be absent for bare repos; present as string if available
+        let workdir = self
+            .bare
+            .workdir()
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|| "<none>".to_string());
+
+        // Human readable filter
+        let filter = match self.filter {
+            ListFilter::All => "All",
+            ListFilter::Modified => "Modified",
+        };
+
+        // Attempt to get number of tracked entries from the index; fall back gracefully on error
+        let tracked_entries = match self.bare.index() {
+            Ok(idx) => idx.len().to_string(),
+            Err(_) => "-".to_string(),
+        };
+
+        write!(
+            f,
+            "Dots {{ git_dir: {git_dir}, workdir: {workdir}, filter: {filter}, tracked_entries: {tracked_entries} }}"
+        )
+    }
+}
+
+impl Dots {
+    pub fn new(git_dir: PathBuf, work_tree: PathBuf) -> Result<Self> {
+        verify_git_installation()?;
+
+        let repo = match Repository::open_bare(&git_dir) {
+            Ok(repo) => repo,
+            Err(_) => Repository::init_bare(&git_dir)?,
+        };
+        repo.set_workdir(&work_tree, false)?;
+
+        Ok(Self {
+            bare: repo,
+            settings: DotsConfig::default(),
+            wait_for_lock: false,
+            dry_run: false,
+            filter: ListFilter::All,
+            filtered_entries: Vec::new(),
+            iter_index: 0,
+        })
+    }
+
+    /// Applies the `[dots]` section of `shelf.toml`.
+    pub fn configure(&mut self, settings: DotsConfig) {
+        self.settings = settings;
+    }
+
+    /// Chooses whether mutating operations block on a held vault lock or fail fast.
+    pub fn set_wait_for_lock(&mut self, wait: bool) {
+        self.wait_for_lock = wait;
+    }
+
+    /// Makes mutating operations report what they would do instead of doing it.
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        self.dry_run = dry_run;
+    }
+
+    /// Takes the advisory lock guarding the index and refs against concurrent
+    /// shelf processes. Dry runs never write, so they don't take it.
+    fn lock(&self) -> Result<Option<VaultLock>> {
+        if self.dry_run {
+            return Ok(None);
+        }
+        VaultLock::acquire(self.bare.path(), self.wait_for_lock).map(Some)
+    }
+
+    /// Fails for operations that have no meaningful preview.
+    fn refuse_dry_run(&self, operation: &str) -> Result<()> {
+        if self.dry_run {
+            return Err(anyhow!("`{operation}` does not support --dry-run"));
+        }
+        Ok(())
+    }
+
+    /// Generic helper for operations that involve iterating over paths, validating, and modifying the index.
+    fn apply_to_paths<F: Fn(&Dots, &Path, &mut Index) -> Result<()>>(
+        &mut self,
+        paths: &[PathBuf],
+        action: F,
+        success_message: &str,
+    ) -> Result<IndexChanges> {
+        let _lock = self.lock()?;
+        let mut index = self.get_index()?;
+        index.read(true)?;
+        if self.dry_run {
+            // Previews stage hashed blobs that are never written; an index
+            // without a repository doesn't check that they exist.
+            index = detached_copy(&index)?;
+        }
+        let before = index_snapshot(&index);
+
+        for path in paths {
+            self.validate_path(path)?;
+            action(self, path, &mut index)?;
+        }
+
+        let changes = self.index_changes(&before, &index_snapshot(&index))?;
+        if self.dry_run {
+            return Ok(changes);
+        }
+
+        self.write_index(&mut index)?;
+        shine_success(success_message);
+        Ok(changes)
+    }
+
+    /// Compares two index snapshots, reporting paths as shown by `list`.
+    fn index_changes(
+        &self,
+        before: &collections::BTreeMap<Vec<u8>, git2::Oid>,
+        after: &collections::BTreeMap<Vec<u8>, git2::Oid>,
+    ) -> Result<IndexChanges> {
+        let mut changes = IndexChanges::default();
+        for (path, id) in after {
+            let bucket = match before.get(path) {
+                None => &mut changes.added,
+                Some(old) if old != id => &mut changes.updated,
+                Some(_) => continue,
+            };
+            bucket.push(self.vault_entry_to_pathbuf(path)?);
+        }
+        for path in before.keys().filter(|path| !after.contains_key(*path)) {
+            changes.removed.push(self.vault_entry_to_pathbuf(path)?);
+        }
+        Ok(changes)
+    }
+
+    /// Tracks the specified paths by adding them to the Git index.
+    pub fn track(&mut self, paths: &[PathBuf]) -> Result<IndexChanges> {
+        self.apply_to_paths(
+            paths,
+            |s, path, index| s.add_path(path, index),
+            "Tabs tracked successfully",
+        )
+    }
+
+    /// Untracks the specified paths by removing them from the Git index.
+    pub fn untrack(&mut self, paths: &[PathBuf]) -> Result<IndexChanges> {
+        self.apply_to_paths(
+            paths,
+            |s, path, index| s.remove_path_or_dir(path, index),
+            "Tabs untracked successfully",
+        )
+    }
+
+    /// Commits staged changes with a default message. In dry-run mode the
+    /// message is returned without creating the commit.
+    pub fn save_local_changes(&self) -> Result<String> {
+        let _lock = self.lock()?;
+        let mut index = self.get_index()?;
+        let statuses = self.repository_status()?;
+
+        if self.is_merging() {
+            return Err(anyhow!(
+                "Merge in progress, run `shelf dots resolve` before saving"
+            ));
+        }
+
+        if self.verify_staged_changes(&statuses).is_err() {
+            return Err(anyhow!("No changes to commit"));
+        }
+
+        let commit_message = self.changes_recap(&statuses);
+        if self.dry_run {
+            return Ok(commit_message);
+        }
+
+        let signature = self.bare.signature()?;
+        let commit_tree = self.prepare_commit_tree(&mut index)?;
+        let parent_commits = self.get_parent_commits()?;
+
+        self.create_commit(&signature, &commit_message, &commit_tree, &parent_commits)?;
+
+        Ok(commit_message)
+    }
+
+    /// Creates and returns a Git tree for the commit
+    fn prepare_commit_tree(&self, index: &mut Index) -> Result<git2::Tree<'_>> {
+        let tree_oid = index.write_tree()?;
+        Ok(self.bare.find_tree(tree_oid)?)
+    }
+
+    /// Creates a new commit with the given components
+    fn create_commit(
+        &self,
+        signature: &git2::Signature,
+        message: &str,
+        tree: &git2::Tree,
+        parents: &[git2::Commit],
+    ) -> Result<git2::Oid> {
+        Ok(self.bare.commit(
+            Some("HEAD"),
+            signature,
+            signature,
+            message,
+            tree,
+            &parents.iter().collect::<Vec<_>>(),
+        )?)
+    }
+
+    /// Fetches `branch` from `remote` and merges it into the vault.
+    ///
+    /// Conflicting files are left in the work tree with markers and the merge is
+    /// only committed by [`Dots::finish_merge`] once everything is resolved.
+    pub fn pull(&self, remote: &str, branch: Option<&str>) -> Result<MergeOutcome> {
+        self.refuse_dry_run("pull")?;
+        let _lock = self.lock()?;
+        if self.is_merging() {
+            return Err(anyhow!(
+                "Merge already in progress, run `shelf dots resolve` first"
+            ));
+        }
+
+        let branch = match branch {
+            Some(branch) => branch.to_string(),
+            None => self.current_branch()?,
+        };
+
+        let git_config = self.bare.config()?;
+        let mut callbacks = git2::RemoteCallbacks::new();
+        callbacks.credentials(|url, username, allowed| {
+            if allowed.contains(git2::CredentialType::SSH_KEY) {
+                git2::Cred::ssh_key_from_agent(username.unwrap_or("git"))
+            } else {
+                git2::Cred::credential_helper(&git_config, url, username)
+            }
+        });
+        let mut fetch_options = git2::FetchOptions::new();
+        fetch_options.remote_callbacks(callbacks);
+
+        self.bare
+            .find_remote(remote)?
+            .fetch(&[&branch], Some(&mut fetch_options), None)?;
+
+        self.merge_reference("FETCH_HEAD")
+    }
+
+    /// Merges the commit `reference` points at into the current vault HEAD.
+    fn merge_reference(&self, reference: &str) -> Result<MergeOutcome> {
+        let reference = self.bare.find_reference(reference)?;
+        let incoming = self.bare.reference_to_annotated_commit(&reference)?;
+        let (analysis, _) = self.bare.merge_analysis(&[&incoming])?;
+
+        if analysis.is_up_to_date() {
+            return Ok(MergeOutcome::UpToDate);
+        }
+
+        if analysis.is_unborn() || analysis.is_fast_forward() {
+            let head = self.bare.find_reference("HEAD")?;
+            let target = head
+                .symbolic_target()
+                .ok_or_else(|| anyhow!("Vault HEAD is detached"))?
+                .to_string();
+            // Check out while HEAD still names the old tree: it is the
+            // baseline, so home files matching it are updated rather than
+            // taken for local edits.
+            let incoming_tree = self.bare.find_commit(incoming.id())?.tree()?;
+            self.bare
+                .checkout_tree(incoming_tree.as_object(), Some(home_checkout().safe()))?;
+            self.bare
+                .reference(&target, incoming.id(), true, "shelf: fast-forward pull")?;
+            self.sync_roots_with_head()?;
+            return Ok(MergeOutcome::FastForward);
+        }
+
+        let mut checkout = home_checkout();
+        checkout
+            .safe()
+            .allow_conflicts(true)
+            .conflict_style_merge(true);
+        self.bare.merge(&[&incoming], None, Some(&mut checkout))?;
+
+        let conflicts = self.conflicted_paths()?;
+        if !conflicts.is_empty() {
+            return Ok(MergeOutcome::Conflicts(conflicts));
+        }
+
+        self.commit_merge()?;
+        Ok(MergeOutcome::Merged)
+    }
+
+    /// Returns `true` while a merge started by [`Dots::pull`] is awaiting resolution.
+    pub fn is_merging(&self) -> bool {
+        self.bare.state() == RepositoryState::Merge
+    }
+
+    /// Lists conflicted paths relative to the work tree.
+    pub fn conflicted_paths(&self) -> Result<Vec<PathBuf>> {
+        let mut index = self.get_index()?;
+        index.read(true)?;
+
+        let mut paths = Vec::new();
+        for conflict in index.conflicts()? {
+            let conflict = conflict?;
+            let entry = conflict
+                .our
+                .or(conflict.their)
+                .or(conflict.ancestor)
+                .ok_or_else(|| anyhow!("Conflict entry without any side"))?;
+            let path = std::str::from_utf8(&entry.path).map_err(|_| Shelfor::InvalidUtf8Path)?;
+            paths.push(PathBuf::from(path));
+        }
+        Ok(paths)
+    }
+
+    /// Resolves a single conflicted path and stages the result.
+    pub fn resolve_conflict(&self, relative: &Path, resolution: Resolution) -> Result<()> {
+        self.refuse_dry_run("resolve")?;
+        let _lock = self.lock()?;
+        let mut index = self.get_index()?;
+        index.read(true)?;
+
+        let conflict = index
+            .conflicts()?
+            .filter_map(Result::ok)
+            .find(|conflict| {
+                [&conflict.our, &conflict.their, &conflict.ancestor]
+                    .into_iter()
+                    .flatten()
+                    .any(|entry| {
+                        Path::new(std::str::from_utf8(&entry.path).unwrap_or("")) == relative
+                    })
+            })
+            .ok_or_else(|| anyhow!("{} is not conflicted", relative.display()))?;
+
+        let full_path = self.workdir()?.join(relative);
+        let chosen = match resolution {
+            Resolution::Ours => Some(conflict.our),
+            Resolution::Theirs => Some(conflict.their),
+            Resolution::Edited => None,
+        };
+
+        // Files under a root are never checked out into the home directory, so
+        // their resolution only happens in the index; `restore` applies it.
+        if relative.starts_with(roots::ROOTS_NAMESPACE) {
+            let Some(chosen) = chosen else {
+                return Err(anyhow!(
+                    "{} lives outside the home directory, pick ours or theirs",
+                    relative.display()
+                ));
+            };
+            index.conflict_remove(relative)?;
+            if let Some(mut entry) = chosen {
+                entry.flags &= !INDEX_STAGE_MASK;
+                index.add(&entry)?;
+            }
+            return self.write_index(&mut index);
+        }
+
+        index.conflict_remove(relative)?;
+        match chosen {
+            // The chosen side deleted the file.
+            Some(None) => {
+                if full_path.exists() {
+                    fs::remove_file(&full_path)?;
+                }
+            }
+            Some(Some(entry)) => {
+                let blob = self.bare.find_blob(entry.id)?;
+                fs::write(&full_path, blob.content())?;
+                index.add_path(relative)?;
+            }
+            None => {
+                let content = fs::read_to_string(&full_path)?;
+                if content
+                    .lines()
+                    .any(|line| line.starts_with(CONFLICT_MARKER))
+                {
+                    return Err(anyhow!(
+                        "{} still contains conflict markers",
+                        relative.display()
+                    ));
+                }
+                index.add_path(relative)?;
+            }
+        }
+
+        self.write_index(&mut index)
+    }
+
+    /// Commits the in-progress merge once every conflict has been resolved.
+    pub fn finish_merge(&self) -> Result<git2::Oid> {
+        self.refuse_dry_run("resolve")?;
+        let _lock = self.lock()?;
+        self.commit_merge()
+    }
+
+    /// Creates the merge commit from the index and `MERGE_HEAD`, then clears the merge state.
+    fn commit_merge(&self) -> Result<git2::Oid> {
+        let mut index = self.get_index()?;
+        index.read(true)?;
+        if index.has_conflicts() {
+            return Err(anyhow!(
+                "Unresolved conflicts remain, run `shelf dots resolve`"
+            ));
+        }
+
+        let mut parents = self.get_parent_commits()?;
+        let merge_heads = fs::read_to_string(self.bare.path().join("MERGE_HEAD"))?;
+        for line in merge_heads.lines().filter(|line| !line.trim().is_empty()) {
+            parents.push(self.bare.find_commit(git2::Oid::from_str(line.trim())?)?);
+        }
+
+        let message = self
+            .bare
+            .message()
+            .unwrap_or_else(|_| "Merge remote vault changes".to_string());
+        let signature = self.bare.signature()?;
+        let tree = self.prepare_commit_tree(&mut index)?;
+        let oid = self.create_commit(&signature, message.trim_end(), &tree, &parents)?;
+
+        self.bare.cleanup_state()?;
+        Ok(oid)
+    }
+
+    /// Returns the short name of the branch HEAD points at, even when unborn.
+    fn current_branch(&self) -> Result<String> {
+        let head = self.bare.find_reference("HEAD")?;
+        head.symbolic_target()
+            .and_then(|target| target.strip_prefix("refs/heads/"))
+            .map(str::to_string)
+            .ok_or_else(|| anyhow!("Vault HEAD is detached"))
+    }
+
+    /// Sets the filter for listing files and resets the iterator.
+    pub fn set_filter(&mut self, filter: ListFilter) {
+        if self.filter != filter {
+            self.filter = filter;
+            self.reset_iterator();
+        }
+    }
+
+    /// Converts a git2::IndexEntry path to a PathBuf relative to the workdir,
+    /// or to its absolute location for entries stored under a root.
+    fn index_entry_to_pathbuf(&self, entry: &git2::IndexEntry) -> Result<PathBuf, Shelfor> {
+        self.vault_entry_to_pathbuf(&entry.path)
+    }
+
+    /// Maps a path as stored in the vault to where it lives on disk.
+    fn vault_entry_to_pathbuf(&self, stored: &[u8]) -> Result<PathBuf, Shelfor> {
+        let path_str = std::str::from_utf8(stored).map_err(|_| Shelfor::InvalidUtf8Path)?;
+        if let Some((root, relative)) = from_vault_path(&self.settings.roots, Path::new(path_str)) {
+            return Ok(root.path.join(relative));
+        }
+        Ok(self.workdir()?.join(path_str))
+    }
+
+    /// Collects filtered entries for iteration based on the current filter.
+    fn collect_filtered_entries(&mut self) -> Result<()> {
+        let index = self.get_index()?;
+        self.filtered_entries = index
+            .iter()
+            .filter(|entry| !roots::is_permissions_record(&entry.path))
+            .filter_map(|entry| {
+                self.index_entry_to_pathbuf(&entry).ok().and_then(|path| {
+                    self.matches_filter(&path)
+                        .ok()
+                        .and_then(|m| m.then_some(path))
+                })
+            })
+            .collect();
+        Ok(())
+    }
+
+    /// Adds a path (file or directory) to the index.
+    fn add_path(&self, path: &Path, index: &mut Index) -> Result<()> {
+        if let Some((root, relative)) = self.root_for(path)? {
+            return self.add_root_path(root, path, &relative, index);
+        }
+
+        if path.is_dir() {
+            self.add_recursive(path, index)
+        } else {
+            self.add_one(path, index)
+        }
+    }
+
+    /// Removes a path (file or directory) from the index.
+    fn remove_path_or_dir(&self, path: &Path, index: &mut Index) -> Result<()> {
+        if let Some((root, relative)) = self.root_for(path)? {
+            index.remove_all([vault_path(root, &relative)?], None)?;
+            return self.update_permissions(root, index, |permissions| {
+                permissions.retain(|tracked, _| !tracked.starts_with(&relative));
+            });
+        }
+
+        let relative = self.get_relative_path(path)?;
+        index.remove_all([relative], None)?;
+        Ok(())
+    }
+
+    /// Checks if a path matches the current filter.
+    fn matches_filter(&self, path: &Path) -> Result<bool> {
+        match self.filter {
+            ListFilter::All => {
+                debug!("Filter: All - path {} matches", path.display());
+                Ok(true)
+            }
+            ListFilter::Modified => {
+                if let Some((root, relative)) = self.root_for(path)? {
+                    return self.root_file_modified(root, path, &relative);
+                }
+
+                let relative = self.get_relative_path(path)?;
+                let status = self.bare.status_file(relative)?;
+                let is_modified = status.contains(git2::Status::WT_MODIFIED);
+                debug!(
+                    "Filter: Modified - path {} is modified: {}",
+                    path.display(),
+                    is_modified
+                );
+                Ok(is_modified)
+            }
+        }
+    }
+
+    /// Generates a summary of changes for the commit message.
+    fn changes_recap(&self, statuses: &Statuses) -> String {
+        let mut msg = String::from("Tracked tabs updated:\n");
+        for entry in statuses.iter() {
+            if let Some(path) = entry.path() {
+                msg.push_str(&format!("  - {:?}: {}\n", entry.status(), path));
+            }
+        }
+        msg
+    }
+
+    /// Validates a path before operations.
+    fn validate_path(&self, path: &Path) -> Result<()> {
+        if !path.exists() {
+            return Err(Shelfor::PathNotFound(path.to_path_buf()).into());
+        }
+        if !path.starts_with(self.workdir()?) && self.root_for(path)?.is_none() {
+            return Err(Shelfor::OutsideWorkTree(path.to_path_buf()).into());
+        }
+        Ok(())
+    }
+
+    /// Returns `true` when `[[dots.roots]]` are configured.
+    pub fn has_roots(&self) -> bool {
+        !self.settings.roots.is_empty()
+    }
+
+    /// Returns the root managing `path` when it lies outside the work tree.
+    fn root_for(&self, path: &Path) -> Result<Option<(&RootConfig, PathBuf)>> {
+        if path.starts_with(self.workdir()?) {
+            return Ok(None);
+        }
+        Ok(find_root(&self.settings.roots, path))
+    }
+
+    /// Stores a file or directory under `root` in the vault namespace, recording
+    /// its permissions so `restore` can recreate it faithfully.
+    fn add_root_path(
+        &self,
+        root: &RootConfig,
+        path: &Path,
+        relative: &Path,
+        index: &mut Index,
+    ) -> Result<()> {
+        let files = if path.is_dir() {
+            WalkDir::new(path)
+                .into_iter()
+                .filter_map(Result::ok)
+                .filter(|entry| !entry.file_type().is_dir())
+                .map(|entry| {
+                    let nested = entry.path().strip_prefix(path)?;
+                    Ok((entry.path().to_path_buf(), relative.join(nested)))
+                })
+                .collect::<Result<Vec<_>>>()?
+        } else {
+            vec![(path.to_path_buf(), relative.to_path_buf())]
+        };
+
+        let mut recorded = Vec::new();
+        for (full_path, file_relative) in files {
+            let meta = fs::symlink_metadata(&full_path)?;
+            let permissions = roots::read_permissions(&full_path)?;
+            let (content, mode) = if meta.file_type().is_symlink() {
+                let target = fs::read_link(&full_path)?;
+                (target.to_string_lossy().into_owned().into_bytes(), 0o120000)
+            } else {
+                let content = roots::read_file(root, &full_path)?;
+                let mode = if permissions.mode & 0o111 != 0 {
+                    0o100755
+                } else {
+                    0o100644
+                };
+                (content, mode)
+            };
+
+            if content.len() as u64 > self.settings.max_file_size {
+                self.report_oversized(&[(full_path.clone(), content.len() as u64)])?;
+            }
+
+            let stored = vault_path(root, &file_relative)?;
+            self.stage_content(index, &stored, mode, &content)?;
+            recorded.push((file_relative, permissions));
+        }
+
+        self.update_permissions(root, index, |permissions| permissions.extend(recorded))
+    }
+
+    /// Loads the permissions record of `root` from the index, applies `update`
+    /// and stages the result.
+    fn update_permissions<F>(&self, root: &RootConfig, index: &mut Index, update: F) -> Result<()>
+    where
+        F: FnOnce(&mut collections::BTreeMap<PathBuf, FilePermissions>),
+    {
+        let record_path = roots::permissions_path(root);
+        let mut permissions = self.read_permissions_record(root, index)?;
+        update(&mut permissions);
+
+        if permissions.is_empty() {
+            // Removing a missing entry is not an error worth surfacing.
+            let _ = index.remove_path(&record_path);
+            return Ok(());
+        }
+
+        let content = roots::format_permissions(&permissions);
+        self.stage_content(index, &record_path, 0o100644, content.as_bytes())
+    }
+
+    /// Stages `content` at `path` in the vault. Dry runs only hash it, so the
+    /// object database is left untouched.
+    fn stage_content(
+        &self,
+        index: &mut Index,
+        path: &Path,
+        mode: u32,
+        content: &[u8],
+    ) -> Result<()> {
+        let id = if self.dry_run {
+            git2::Oid::hash_object(git2::ObjectType::Blob, content)?
+        } else {
+            self.bare.blob(content)?
+        };
+        index.add(&git2::IndexEntry {
+            ctime: git2::IndexTime::new(0, 0),
+            mtime: git2::IndexTime::new(0, 0),
+            dev: 0,
+            ino: 0,
+            mode,
+            uid: 0,
+            gid: 0,
+            file_size: u32::try_from(content.len()).unwrap_or(u32::MAX),
+            id,
+            flags: 0,
+            flags_extended: 0,
+            path: path
+                .to_str()
+                .ok_or(Shelfor::InvalidUtf8Path)?
+                .replace('\\', "/")
+                .into_bytes(),
+        })?;
+        Ok(())
+    }
+
+    /// Stages a work tree file for a dry run without writing its blob.
+    fn preview_work_file(&self, relative: &Path, index: &mut Index) -> Result<()> {
+        let full_path = self.workdir()?.join(relative);
+        let meta = fs::symlink_metadata(&full_path)?;
+        if meta.file_type().is_symlink() {
+            let target = fs::read_link(&full_path)?;
+            let target = target.to_string_lossy().into_owned();
+            return self.stage_content(index, relative, 0o120000, target.as_bytes());
+        }
+        let mode = if roots::read_permissions(&full_path)?.mode & 0o111 != 0 {
+            0o100755
+        } else {
+            0o100644
+        };
+        self.stage_content(index, relative, mode, &fs::read(&full_path)?)
+    }
+
+    /// Reads the permissions record of `root` as currently staged.
+    fn read_permissions_record(
+        &self,
+        root: &RootConfig,
+        index: &Index,
+    ) -> Result<collections::BTreeMap<PathBuf, FilePermissions>> {
+        let Some(entry) = index.get_path(&roots::permissions_path(root), 0) else {
+            return Ok(collections::BTreeMap::new());
+        };
+        let blob = self.bare.find_blob(entry.id)?;
+        Ok(roots::parse_permissions(&String::from_utf8_lossy(
+            blob.content(),
+        )))
+    }
+
+    /// Compares a file under a root with its staged content.
+    fn root_file_modified(&self, root: &RootConfig, path: &Path, relative: &Path) -> Result<bool> {
+        let index = self.get_index()?;
+        let Some(entry) = index.get_path(&vault_path(root, relative)?, 0) else {
+            return Ok(false);
+        };
+
+        let current = if fs::symlink_metadata(path)?.file_type().is_symlink() {
+            let target = fs::read_link(path)?;
+            git2::Oid::hash_object(git2::ObjectType::Blob, target.to_string_lossy().as_bytes())?
+        } else {
+            match git2::Oid::hash_file(git2::ObjectType::Blob, path) {
+                Ok(oid) => oid,
+                // Unreadable without escalation; don't prompt just to list.
+                Err(_) => return Ok(false),
+            }
+        };
+        Ok(current != entry.id)
+    }
+
+    /// Writes staged files under the configured roots back to disk with their
+    /// recorded permissions. Returns the absolute paths that were written.
+    pub fn restore_roots(&self, only: &[PathBuf]) -> Result<Vec<PathBuf>> {
+        let index = self.get_index()?;
+        let mut restored = Vec::new();
+
+        for root in &self.settings.roots {
+            let permissions = self.read_permissions_record(root, &index)?;
+            let namespace = Path::new(roots::ROOTS_NAMESPACE).join(&root.name);
+
+            for entry in index.iter() {
+                let stored =
+                    std::str::from_utf8(&entry.path).map_err(|_| Shelfor::InvalidUtf8Path)?;
+                let Ok(relative) = Path::new(stored).strip_prefix(&namespace) else {
+                    continue;
+                };
+                if roots::is_permissions_record(&entry.path) {
+                    continue;
+                }
+
+                let target = root.path.join(relative);
+                if !only.is_empty() && !only.iter().any(|path| target.starts_with(path)) {
+                    continue;
+                }
+
+                if self.dry_run {
+                    restored.push(target);
+                    continue;
+                }
+
+                let blob = self.bare.find_blob(entry.id)?;
+                if entry.mode == 0o120000 {
+                    restore_symlink(&target, blob.content())?;
+                } else {
+                    roots::write_file(
+                        root,
+                        &target,
+                        blob.content(),
+                        permissions.get(relative).copied(),
+                    )?;
+                }
+                restored.push(target);
+            }
+        }
+
+        Ok(restored)
+    }
+
+    /// Brings index entries under the roots namespace in line with HEAD after a
+    /// fast-forward, since checkout deliberately skips them.
+    fn sync_roots_with_head(&self) -> Result<()> {
+        let mut index = self.get_index()?;
+        index.read(true)?;
+        index.remove_dir(Path::new(roots::ROOTS_NAMESPACE), 0)?;
+
+        let tree = self.bare.head()?.peel_to_tree()?;
+        let Ok(namespace) = tree.get_path(Path::new(roots::ROOTS_NAMESPACE)) else {
+            return self.write_index(&mut index);
+        };
+        let namespace = self.bare.find_tree(namespace.id())?;
+
+        let mut entries = Vec::new();
+        namespace.walk(git2::TreeWalkMode::PreOrder, |dir, entry| {
+            if entry.kind() == Some(git2::ObjectType::Blob) {
+                entries.push((
+                    format!(
+                        "{}/{dir}{}",
+                        roots::ROOTS_NAMESPACE,
+                        entry.name().unwrap_or_default()
+                    ),
+                    entry.id(),
+                    entry.filemode() as u32,
+                ));
+            }
+            git2::TreeWalkResult::Ok
+        })?;
+
+        for (path, id, mode) in entries {
+            index.add(&git2::IndexEntry {
+                ctime: git2::IndexTime::new(0, 0),
+                mtime: git2::IndexTime::new(0, 0),
+                dev: 0,
+                ino: 0,
+                mode,
+                uid: 0,
+                gid: 0,
+                file_size: 0,
+                id,
+                flags: 0,
+                flags_extended: 0,
+                path: path.into_bytes(),
+            })?;
+        }
+        self.write_index(&mut index)
+    }
+
+    /// Adds a single file to the index.
+    fn add_one(&self, path: &Path, index: &mut Index) -> Result<()> {
+        let relative = self.get_relative_path(path)?;
+        let size = fs::symlink_metadata(path)?.len();
+        if size > self.settings.max_file_size {
+            self.report_oversized(&[(relative.to_path_buf(), size)])?;
+        }
+        if self.dry_run {
+            return self.preview_work_file(relative, index);
+        }
+        index.add_path(relative)?;
+        Ok(())
+    }
+
+    /// Recursively adds all files in a directory to the index.
+    fn add_recursive(&self, path: &Path, index: &mut Index) -> Result<()> {
+        let relative = self.get_relative_path(path)?;
+        let workdir = self.workdir()?;
+        let mut oversized = Vec::new();
+        let mut check_size = |entry: &Path, _spec: &[u8]| -> i32 {
+            match fs::symlink_metadata(workdir.join(entry)) {
+                Ok(meta) if meta.is_file() && meta.len() > self.settings.max_file_size => {
+                    oversized.push((entry.to_path_buf(), meta.len()));
+                    // Skip the entry when refusing; the error is raised below.
+                    i32::from(self.settings.refuse_oversized)
+                }
+                _ => 0,
+            }
+        };
+        if self.dry_run {
+            // `add_all` writes every blob, so walk the directory by hand.
+            let files = WalkDir::new(workdir.join(relative))
+                .into_iter()
+                .filter_entry(|entry| entry.path() != self.bare.path())
+                .filter_map(Result::ok)
+                .filter(|entry| !entry.file_type().is_dir());
+            for entry in files {
+                let entry_relative = entry.path().strip_prefix(workdir)?;
+                if !self.bare.is_path_ignored(entry_relative)?
+                    && check_size(entry_relative, &[]) == 0
+                {
+                    self.preview_work_file(entry_relative, index)?;
+                }
+            }
+        } else {
+            index.add_all(
+                [relative],
+                git2::IndexAddOption::DEFAULT,
+                Some(&mut check_size),
+            )?;
+        }
+
+        if !oversized.is_empty() {
+            self.report_oversized(&oversized)?;
+        }
+        Ok(())
+    }
+
+    /// Warns about, or refuses, files above the configured size threshold.
+    fn report_oversized(&self, files: &[(PathBuf, u64)]) -> Result<()> {
+        let limit = format_size(self.settings.max_file_size);
+        if self.settings.refuse_oversized {
+            let listing = files
+                .iter()
+                .map(|(path, size)| format!("  {} ({})", path.display(), format_size(*size)))
+                .collect::<Vec<_>>()
+                .join("\n");
+            return Err(anyhow!(
+                "Refusing to track files larger than {limit}:\n{listing}"
+            ));
+        }
+
+        for (path, size) in files {
+            eprintln!(
+                "{} {} is {} (threshold {limit})",
+                "warning:".bright_yellow().bold(),
+                path.display(),
+                format_size(*size)
+            );
+        }
+        Ok(())
+    }
+
+    /// Collects the largest tracked files, the largest blobs reachable from
+    /// any commit and the on-disk size of the vault.
+    pub fn disk_usage(&self, limit: usize) -> Result<DiskUsage> {
+        let index = self.get_index()?;
+        let odb = self.bare.odb()?;
+        let mut tracked = index
+            .iter()
+            .map(|entry| {
+                let path =
+                    std::str::from_utf8(&entry.path).map_err(|_| Shelfor::InvalidUtf8Path)?;
+                // The index caps sizes at `u32::MAX`; the blob has the real one.
+                let size = odb
+                    .read_header(entry.id)
+                    .map_or(u64::from(entry.file_size), |(size, _)| size as u64);
+                Ok((PathBuf::from(path), size))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        tracked.sort_by_key(|entry| std::cmp::Reverse(entry.1));
+        tracked.truncate(limit);
+
+        let mut blobs: collections::HashMap<git2::Oid, (PathBuf, u64)> =
+            collections::HashMap::new();
+        if self.bare.head().is_ok() {
+            let mut revwalk = self.bare.revwalk()?;
+            revwalk.push_head()?;
+            for oid in revwalk {
+                let tree = self.bare.find_commit(oid?)?.tree()?;
+                tree.walk(git2::TreeWalkMode::PreOrder, |root, entry| {
+                    if entry.kind() == Some(git2::ObjectType::Blob)
+                        && !blobs.contains_key(&entry.id())
+                        && let Ok((size, _)) = odb.read_header(entry.id())
+                    {
+                        let path = Path::new(root).join(entry.name().unwrap_or_default());
+                        blobs.insert(entry.id(), (path, size as u64));
+                    }
+                    git2::TreeWalkResult::Ok
+                })?;
+            }
+        }
+        let mut history = blobs.into_values().collect::<Vec<_>>();
+        history.sort_by_key(|entry| std::cmp::Reverse(entry.1));
+        history.truncate(limit);
+
+        let on_disk = WalkDir::new(self.bare.path())
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter_map(|entry| entry.metadata().ok())
+            .filter(|meta| meta.is_file())
+            .map(|meta| meta.len())
+            .sum();
+
+        Ok(DiskUsage {
+            tracked,
+            history,
+            on_disk,
+        })
+    }
+
+    /// Rewrites every branch, tag and other ref so that `path` never existed,
+    /// deletes the remote-tracking refs, drops it from the index and repacks
+    /// the vault. Returns the number of rewritten commits.
+    pub fn purge(&self, path: &Path) -> Result<usize> {
+        let _lock = self.lock()?;
+        if self.is_merging() {
+            return Err(anyhow!(
+                "Merge in progress, run `shelf dots resolve` before purging"
+            ));
+        }
+
+        let absolute = if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            std::env::current_dir()?.join(path)
+        };
+        let relative = self.get_relative_path(&absolute)?.to_path_buf();
+        let components = relative
+            .iter()
+            .map(|part| part.to_str().ok_or(Shelfor::InvalidUtf8Path))
+            .collect::<Result<Vec<_>, _>>()?;
+        if components.is_empty() {
+            return Err(anyhow!("Refusing to purge the whole vault"));
+        }
+
+        // Remote-tracking refs are dropped rather than rewritten, so they
+        // are left out of the walk; everything else keeps its commits.
+        let mut revwalk = self.bare.revwalk()?;
+        for reference in self.bare.references()? {
+            let reference = reference?;
+            if reference
+                .name()
+                .is_some_and(|name| name.starts_with("refs/remotes/"))
+            {
+                continue;
+            }
+            if let Ok(commit) = reference.peel_to_commit() {
+                revwalk.push(commit.id())?;
+            }
+        }
+        revwalk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::REVERSE)?;
+        let commits = revwalk.collect::<Result<Vec<_>, _>>()?;
+
+        if self.dry_run {
+            // Count without writing: a commit is rewritten when its tree holds
+            // the path or one of its parents is rewritten.
+            let mut affected = collections::HashSet::new();
+            for oid in &commits {
+                let commit = self.bare.find_commit(*oid)?;
+                if commit.tree()?.get_path(&relative).is_ok()
+                    || commit.parent_ids().any(|parent| affected.contains(&parent))
+                {
+                    affected.insert(*oid);
+                }
+            }
+            return Ok(affected.len());
+        }
+
+        let mut rewritten: collections::HashMap<git2::Oid, git2::Oid> = collections::HashMap::new();
+        let mut changed = 0;
+        for oid in commits {
+            let commit = self.bare.find_commit(oid)?;
+            let tree = commit.tree()?;
+            let new_tree_id = self.tree_without(&tree, &components)?;
+            let parents = commit
+                .parent_ids()
+                .map(|parent| {
+                    self.bare
+                        .find_commit(*rewritten.get(&parent).unwrap_or(&parent))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let parents_unchanged = parents
+                .iter()
+                .zip(commit.parent_ids())
+                .all(|(new, old)| new.id() == old);
+            if new_tree_id == tree.id() && parents_unchanged {
+                rewritten.insert(commit.id(), commit.id());
+                continue;
+            }
+
+            let new_tree = self.bare.find_tree(new_tree_id)?;
+            let new_id = self.bare.commit(
+                None,
+                &commit.author(),
+                &commit.committer(),
+                commit.message().unwrap_or_default(),
+                &new_tree,
+                &parents.iter().collect::<Vec<_>>(),
+            )?;
+            rewritten.insert(commit.id(), new_id);
+            changed += 1;
+        }
+
+        self.retarget_references(&rewritten)?;
+
+        let mut index = self.get_index()?;
+        index.remove_all([&relative], None)?;
+        self.write_index(&mut index)?;
+
+        self.repack()?;
+        Ok(changed)
+    }
+
+    /// Points every ref at its rewritten commit and deletes remote-tracking
+    /// refs and the `FETCH_HEAD`/`ORIG_HEAD` leftovers, so nothing keeps the
+    /// old history reachable.
+    fn retarget_references(
+        &self,
+        rewritten: &collections::HashMap<git2::Oid, git2::Oid>,
+    ) -> Result<()> {
+        for reference in self.bare.references()? {
+            let mut reference = reference?;
+            let Some(name) = reference.name().map(str::to_string) else {
+                continue;
+            };
+            if name.starts_with("refs/remotes/") {
+                reference.delete()?;
+                continue;
+            }
+            let Some(old) = reference.target() else {
+                continue;
+            };
+
+            // Annotated tags point at a tag object; recreate it around the new commit.
+            if let Ok(tag) = self.bare.find_tag(old) {
+                let Some(new_id) = rewritten.get(&tag.target_id()) else {
+                    continue;
+                };
+                if *new_id == tag.target_id() {
+                    continue;
+                }
+                let target = self.bare.find_object(*new_id, None)?;
+                let tagger = tag
+                    .tagger()
+                    .map(|tagger| tagger.to_owned())
+                    .map_or_else(|| self.bare.signature(), Ok)?;
+                self.bare.tag(
+                    tag.name().unwrap_or_default(),
+                    &target,
+                    &tagger,
+                    tag.message().unwrap_or_default(),
+                    true,
+                )?;
+            } else if let Some(new_id) = rewritten.get(&old)
+                && *new_id != old
+            {
+                reference.set_target(*new_id, "shelf: purge")?;
+            }
+        }
+
+        for leftover in ["FETCH_HEAD", "ORIG_HEAD"] {
+            match fs::remove_file(self.bare.path().join(leftover)) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the id of `tree` with the entry at `components` removed.
+    fn tree_without(&self, tree: &git2::Tree, components: &[&str]) -> Result<git2::Oid> {
+        let Some((first, rest)) = components.split_first() else {
+            return Ok(tree.id());
+        };
+        let Some(entry) = tree.get_name(first) else {
+            return Ok(tree.id());
+        };
+
+        let mut builder = self.bare.treebuilder(Some(tree))?;
+        if rest.is_empty() {
+            builder.remove(first)?;
+        } else if entry.kind() == Some(git2::ObjectType::Tree) {
+            let subtree = self.bare.find_tree(entry.id())?;
+            let new_subtree = self.tree_without(&subtree, rest)?;
+            if new_subtree == subtree.id() {
+                return Ok(tree.id());
+            }
+            if self.bare.find_tree(new_subtree)?.is_empty() {
+                builder.remove(first)?;
+            } else {
+                builder.insert(first, new_subtree, entry.filemode())?;
+            }
+        } else {
+            return Ok(tree.id());
+        }
+        Ok(builder.write()?)
+    }
+
+    /// Expires reflogs and runs `git gc` so purged objects are actually dropped.
+    fn repack(&self) -> Result<()> {
+        let steps: [&[&str]; 2] = [
+            &["reflog", "expire", "--expire=now", "--all"],
+            &["gc", "--prune=now", "--quiet"],
+        ];
+        for args in steps {
+            let status = Command::new("git")
+                .arg("--git-dir")
+                .arg(self.bare.path())
+                .args(args)
+                .status()?;
+            if !status.success() {
+                return Err(anyhow!("`git {}` failed while repacking", args.join(" ")));
+            }
+        }
+        Ok(())
+    }
+
+    /// Retrieves the parent commits for the current HEAD.
+    fn get_parent_commits(&self) -> Result<Vec<git2::Commit<'_>>> {
+        match self.bare.head() {
+            Ok(head) => match head.target() {
+                Some(oid) => match self.bare.find_commit(oid) {
+                    Ok(commit) => Ok(vec![commit]),
+                    Err(e) => Err(e.into()),
+                },
+                None => Ok(vec![]),
+            },
+            Err(_) => Ok(vec![]),
+        }
+    }
+
+    /// Retrieves the repository index.
+    fn get_index(&self) -> Result<Index> {
+        Ok(self.bare.index().map_err(Shelfor::Git)?)
+    }
+
+    /// Writes the index to disk.
+    fn write_index(&self, index: &mut Index) -> Result<()> {
+        index.write().map_err(Shelfor::Git)?;
+        Ok(())
+    }
+
+    /// Gets the working directory of the repository.
+    fn workdir(&self) -> Result<&Path, Shelfor> {
+        self.bare.workdir().ok_or(Shelfor::GitNotInstalled)
+    }
+
+    /// Computes the relative path from the working directory.
+    fn get_relative_path<'a>(&self, path: &'a Path) -> Result<&'a Path, Shelfor> {
+        Ok(path.strip_prefix(self.workdir()?)?)
+    }
+
+    /// Paths staged for the next save, as shown by `list`.
+    fn staged_paths(&self) -> Result<Vec<PathBuf>> {
+        let statuses = self.repository_status()?;
+        statuses
+            .iter()
+            .filter_map(|entry| entry.path().map(str::to_string))
+            .filter(|path| !roots::is_permissions_record(path.as_bytes()))
+            .map(|path| Ok(self.vault_entry_to_pathbuf(path.as_bytes())?))
+            .collect()
+    }
+
+    /// Retrieves the repository status.
+    fn repository_status(&self) -> Result<Statuses<'_>> {
+        let mut opts = git2::StatusOptions::new();
+        opts.include_ignored(false)
+            .include_untracked(false)
+            .include_unmodified(false)
+            .show(git2::StatusShow::Index);
+        Ok(self.bare.statuses(Some(&mut opts))?)
+    }
+
+    /// Checks if a Git status entry indicates a staged or modified change.
+    fn is_staged_or_modified(&self, status: git2::Status) -> bool {
+        status.contains(git2::Status::INDEX_NEW)
+            || status.contains(git2::Status::INDEX_MODIFIED)
+            || status.contains(git2::Status::INDEX_DELETED)
+            || status.contains(git2::Status::WT_MODIFIED)
+            || status.contains(git2::Status::WT_NEW)
+    }
+
+    /// Verifies that there are staged changes to commit.
+    fn verify_staged_changes(&self, statuses: &Statuses) -> Result<()> {
+        let has_changes = statuses
+            .iter()
+            .any(|entry| self.is_staged_or_modified(entry.status()));
+
+        has_changes
+            .then_some(())
+            .ok_or_else(|| anyhow!("No staged changes to commit"))
+    }
+
+    /// Resets the iterator state.
+    fn reset_iterator(&mut self) {
+        self.iter_index = 0;
+        self.filtered_entries.clear();
+    }
+}
+
+impl Iterator for Dots {
+    type Item = PathBuf;
+
+    /// Returns the next file path matching the current filter, or `None` if exhausted.
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.iter_index == 0 {
+            let _ = self.collect_filtered_entries();
+        }
+
+        self.filtered_entries
+            .get(self.iter_index)
+            .cloned()
+            .inspect(|_| {
+                self.iter_index += 1;
+            })
+    }
+}
+
+/// Checkout options that leave the roots namespace out of the home directory.
+fn home_checkout() -> CheckoutBuilder<'static> {
+    let mut checkout = CheckoutBuilder::new();
+    checkout
+        .path(format!("!{}/*", roots::ROOTS_NAMESPACE))
+        .path("*");
+    checkout
+}
+
+/// Recreates a tracked symlink pointing at `target`.
+#[cfg(unix)]
+fn restore_symlink(path: &Path, target: &[u8]) -> Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+    if fs::symlink_metadata(path).is_ok() {
+        fs::remove_file(path)?;
+    }
+    std::os::unix::fs::symlink(std::ffi::OsStr::from_bytes(target), path)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn restore_symlink(path: &Path, target: &[u8]) -> Result<()> {
+    Err(anyhow!(
+        "Cannot restore symlink {} -> {}",
+        path.display(),
+        String::from_utf8_lossy(target)
+    ))
+}
+
+/// Records every index entry's path and blob id for later comparison.
+/// An in-memory copy of `index`, unattached to any repository.
+fn detached_copy(index: &Index) -> Result<Index> {
+    let mut copy = Index::new()?;
+    for entry in index.iter() {
+        copy.add(&entry)?;
+    }
+    Ok(copy)
+}
+
+fn index_snapshot(index: &Index) -> collections::BTreeMap<Vec<u8>, git2::Oid> {
+    index
+        .iter()
+        .filter(|entry| !roots::is_permissions_record(&entry.path))
+        .map(|entry| (entry.path, entry.id))
+        .collect()
+}
+
+/// Index entries a track or untrack call added, updated or removed.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct IndexChanges {
+    /// Paths that were not tracked before.
+    pub added: Vec<PathBuf>,
+    /// Tracked paths whose staged content changed.
+    pub updated: Vec<PathBuf>,
+    /// Paths that are no longer tracked.
+    pub removed: Vec<PathBuf>,
+}
+
+/// Space usage report produced by [`Dots::disk_usage`].
+#[derive(Debug)]
+pub struct DiskUsage {
+    /// Largest files in the index, relative to the work tree.
+    pub tracked: Vec<(PathBuf, u64)>,
+    /// Largest blobs reachable from HEAD, with the first path they were seen at.
+    pub history: Vec<(PathBuf, u64)>,
+    /// Total size of the vault's git directory.
+    pub on_disk: u64,
+}
+
+/// Result of merging fetched vault history.
+#[derive(Debug, PartialEq, Eq)]
+pub enum MergeOutcome {
+    /// Nothing new to merge.
+    UpToDate,
+    /// HEAD was moved forward without a merge commit.
+    FastForward,
+    /// A merge commit was created without conflicts.
+    Merged,
+    /// The merge stopped with conflicts in these work tree relative paths.
+    Conflicts(Vec<PathBuf>),
+}
+
+/// How a conflicted path should be resolved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    /// Keep the local version.
+    Ours,
+    /// Take the incoming version.
+    Theirs,
+    /// The work tree file was edited by hand and should be staged as is.
+    Edited,
+}
+
+/// Filtering criteria for repository listings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListFilter {
+    /// Show all tracked files without filtering.
+    All,
+    /// Only show files modified in the working tree.
+    Modified,
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::git::verify_git_installation;
+
+    use super::*;
+    #[cfg(unix)]
+    use std::os::unix::fs::symlink;
+    #[cfg(windows)]
+    use std::os::windows::fs::symlink_file as symlink;
+    use std::{env, fs};
+    use tempfile::tempdir;
+    use tracing::debug;
+
+    const SHELF_BARE_NAME: &str = ".shelf";
+
+    struct TestEnv {
+        manager: Dots,
+        _temp: tempfile::TempDir,
+    }
+
+    impl TestEnv {
+        pub fn new() -> Result<Self> {
+            // Initialize logging if needed
+            init_test_logging();
+
+            // Create a temporary directory
+            let temp_dir = tempdir()?;
+            let work_tree = temp_dir.path();
+            let git_dir = work_tree.join(SHELF_BARE_NAME);
+
+            // Initialize a bare repository in the temp directory
+            fs::create_dir_all(&git_dir)?;
+            let repo = Repository::init_bare(&git_dir)?;
+            repo.set_workdir(work_tree, false)?;
+
+            let mut config = repo.config()?;
+            config.set_str("user.name", "Test User")?;
+            config.set_str("user.email", "test@example.com")?;
+
+            // Create RepositoryManager with the isolated repository
+            let manager = Dots {
+                bare: repo,
+                settings: DotsConfig::default(),
+                wait_for_lock: false,
+                dry_run: false,
+                filter: ListFilter::All,
+                filtered_entries: Vec::new(),
+                iter_index: 0,
+            };
+
+            Ok(Self {
+                _temp: temp_dir,
+                manager,
+            })
+        }
+
+        pub fn workdir(&self) -> &Path {
+            self.manager.bare.workdir().expect("Valid workdir")
+        }
+
+        pub fn create_test_file(&self, path: &str) -> PathBuf {
+            let full_path = self.workdir().join(path);
+            fs::create_dir_all(full_path.parent().unwrap()).unwrap();
+            fs::write(&full_path, "test content").unwrap();
+            full_path
+        }
+
+        fn create_test_dir(&self, path: &str) -> PathBuf {
+            debug!("Creating test directory: {}", path);
+            let full_path = self.workdir().join(path);
+            fs::create_dir_all(&full_path).unwrap();
+            full_path
+        }
+
+        #[cfg(not(windows))]
+        fn create_symlink(&self, target: &Path, link_name: &str) -> PathBuf {
+            debug!("Creating symlink: {} -> {}", link_name, target.display());
+            let link_path = self.workdir().join(link_name);
+            symlink(target, &link_path).unwrap();
+            link_path
+        }
+
+        fn tracked_paths(&mut self) -> Vec<PathBuf> {
+            debug!("Getting tracked paths");
+            // Reset index before getting paths to ensure fresh repository state
+            self.manager.reset_iterator();
+
+            let mut result = Vec::new();
+            while let Some(path) = self.manager.next() {
+                result.push(path);
+            }
+
+            // Reset again after collection
+            self.manager.reset_iterator();
+            result.sort(); // Sort paths for consistent comparison
+            result
+        }
+    }
+
+    fn init_test_logging() {
+        let _ = tracing_subscriber::fmt()
+            .with_test_writer()
+            .with_max_level(tracing::Level::DEBUG)
+            .try_init();
+    }
+
+    #[test]
+    fn git_installation_detection() -> Result<()> {
+        debug!("Testing git installation detection");
+        let original_path = env::var_os("PATH");
+
+        // SAFETY: Test environment should run without concurrent access
+        unsafe { env::set_var("PATH", "") }
+        let err = verify_git_installation().unwrap_err();
+        assert!(matches!(err, Shelfor::GitNotInstalled));
+
+        // SAFETY: Test environment should run without concurrent access
+        // Restoring original PATH value
+        unsafe { env::set_var("PATH", original_path.unwrap_or_default()) }
+        assert!(verify_git_installation().is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn repository_initialization_creates_required_files() -> Result<()> {
+        debug!("Testing repository initialization");
+        let env = TestEnv::new()?;
+        let repo_path = env.manager.bare.path();
+
+        assert!(repo_path.exists(), "Repository directory missing");
+        assert!(repo_path.join("HEAD").exists(), "HEAD file missing");
+        assert!(repo_path.join("config").exists(), "Config file missing");
+        assert!(
+            repo_path.join("objects").exists(),
+            "Objects directory missing"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn tracking_untracking_single_file() -> Result<()> {
+        debug!("Testing tracking/untracking single file");
+        let mut env = TestEnv::new()?;
+
+        let test_file = env.create_test_file("test.txt");
+
+        // Clear out any existing index state
+        if let Ok(mut index) = env.manager.bare.index() {
+            index.clear()?;
+        }
+
+        // Track and verify
+        env.manager.track(&[test_file.clone()])?;
+        let tracked = env.tracked_paths();
+        let expected = vec![test_file.clone()];
+        assert_eq!(tracked, expected, "Tracked paths did not match expected");
+
+        // Untrack and verify
+        env.manager.untrack(&[test_file.clone()])?;
+        let empty_paths = env.tracked_paths();
+        assert!(
+            empty_paths.is_empty(),
+            "Paths should be empty after untracking"
+        );
+        assert!(test_file.exists(), "File should persist");
+
+        Ok(())
+    }
+
+    #[test]
+    fn tracking_invalid_paths_returns_proper_errors() -> Result<()> {
+        debug!("Testing invalid path handling");
+        let mut env = TestEnv::new()?;
+
+        // Non-existent path
+        let missing = env.workdir().join("ghost.txt");
+        let err = env.manager.track(&[missing]).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Shelfor>(),
+            Some(Shelfor::PathNotFound(_))
+        ));
+
+        // External path
+        let external = if cfg!(windows) {
+            PathBuf::from("C:\\Windows\\System32\\drivers\\etc\\hosts")
+        } else {
+            PathBuf::from("/etc/passwd")
+        };
+        let err = env.manager.track(&[external]).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Shelfor>(),
+            Some(Shelfor::OutsideWorkTree(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn nested_directory_tracking_includes_all_files() -> Result<()> {
+        debug!("Testing nested directory tracking");
+        let mut env = TestEnv::new()?;
+
+        // Configure git user for commits before any operations
+        env.manager.bare.config()?.set_str("user.name", "test")?;
+        env.manager
+            .bare
+            .config()?
+            .set_str("user.email", "test@example.com")?;
+
+        let dir = env.create_test_dir("nested/directory");
+
+        let file1 = env.create_test_file("nested/directory/file1.txt");
+        let file2 = env.create_test_file("nested/directory/.hidden");
+        let file3 = env.create_test_file("nested/directory/.gitignore");
+
+        // Track directory
+        env.manager.track(&[dir])?;
+        let tracked = env.tracked_paths();
+
+        assert!(tracked.contains(&file1), "Normal file missing");
+        assert!(tracked.contains(&file2), "Hidden file missing");
+        assert!(tracked.contains(&file3), "Gitignore file missing");
+
+        Ok(())
+    }
+
+    #[test]
+    fn modified_files_filter_shows_changes() -> Result<()> {
+        debug!("Testing modified files filter");
+        let mut env = TestEnv::new()?;
+
+        // Configure git user for commits before any operations
+        env.manager.bare.config()?.set_str("user.name", "test")?;
+        env.manager
+            .bare
+            .config()?
+            .set_str("user.email", "test@example.com")?;
+
+        let test_file = env.create_test_file("test.txt");
+
+        // Configure git user for commits
+        env.manager.bare.config()?.set_str("user.name", "test")?;
+        env.manager
+            .bare
+            .config()?
+            .set_str("user.email", "test@example.com")?;
+
+        // Initial tracking and commit
+        env.manager.track(&[test_file.clone()])?;
+        env.manager.save_local_changes()?;
+
+        env.manager.set_filter(ListFilter::Modified);
+
+        // Should show no changes initially
+        assert!(env.tracked_paths().is_empty(), "Initial state");
+
+        // Modify file
+        fs::write(&test_file, "new content")?;
+
+        let tracked = env.tracked_paths();
+
+        assert_eq!(tracked.len(), 1, "After modification");
+        assert_eq!(tracked[0], test_file);
+
+        // Stage changes
+        env.manager.track(&[test_file.clone()])?;
+        env.manager.save_local_changes()?;
+
+        assert!(
+            env.tracked_paths().is_empty(),
+            "Should be empty after committing"
+        );
+
+        Ok(())
+    }
+    #[test]
+    fn special_files_handling() -> Result<()> {
+        debug!("Testing special files handling");
+        let mut env = TestEnv::new()?;
+        let target = env.create_test_file("target.txt");
+
+        env.manager.track(&[target.clone()])?;
+
+        // Store tracked paths and sort for consistent comparison
+        let mut tracked = env.tracked_paths();
+        tracked.sort();
+        let mut expected = vec![target];
+        expected.sort();
+
+        assert_eq!(tracked, expected, "Tracked paths did not match expected");
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)] // Skip on Windows since it doesn't support empty directory tracking
+    fn empty_directory_tracking() -> Result<()> {
+        debug!("Testing empty directory tracking");
+        let mut env = TestEnv::new()?;
+        let empty_dir = env.create_test_dir("empty");
+
+        env.manager.track(&[empty_dir])?;
+        assert!(env.tracked_paths().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn filter_mode_selection() -> Result<()> {
+        debug!("Testing filter mode selection");
+        let mut env = TestEnv::new()?;
+
+        // Configure git user for commits
+        env.manager.bare.config()?.set_str("user.name", "test")?;
+        env.manager
+            .bare
+            .config()?
+            .set_str("user.email", "test@example.com")?;
+
+        let test_file = env.create_test_file("test.txt");
+
+        // Track file with All filter
+        env.manager.set_filter(ListFilter::All);
+        env.manager.track(&[test_file.clone()])?;
+
+        // Verify All filter shows file
+        let mut tracked = env.tracked_paths();
+        assert!(!tracked.is_empty(), "All filter should show tracked files");
+        tracked.clear();
+
+        // Switch to Modified filter
+        env.manager.set_filter(ListFilter::Modified);
+
+        // Verify Modified filter shows no changes
+        assert!(
+            env.tracked_paths().is_empty(),
+            "Modified filter should show no changes for unmodified files"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)] // Only run on Unix systems
+    fn symlink_tracking() -> Result<()> {
+        debug!("Testing symlink tracking");
+        let mut env = TestEnv::new()?;
+
+        // Configure git user for commits
+        env.manager.bare.config()?.set_str("user.name", "test")?;
+        env.manager
+            .bare
+            .config()?
+            .set_str("user.email", "test@example.com")?;
+
+        let target = env.create_test_file("target.txt");
+        let link = env.create_symlink(&target, "link.txt");
+
+        // Track only the symlink
+        env.manager.track(&[link.clone()])?;
+
+        // Get tracked paths to verify only symlink was tracked
+        let tracked = env.tracked_paths();
+
+        assert!(tracked.contains(&link), "Symlink should be tracked");
+        assert!(
+            !tracked.contains(&target),
+            "Target file should not be tracked"
+        );
+
+        // Verify symlink points to right target
+        let metadata = fs::symlink_metadata(&link)?;
+        assert!(metadata.file_type().is_symlink(), "Should be symlink");
+
+        // Untrack symlink
+        env.manager.untrack(&[link.clone()])?;
+        assert!(env.tracked_paths().is_empty(), "No paths should be tracked");
+
+        // Original files should still exist
+        assert!(target.exists(), "Target file should exist");
+        assert!(link.exists(), "Symlink should exist");
+
+        Ok(())
+    }
+
+    #[test]
+    fn tracking_multiple_files_and_directories() -> Result<()> {
+        let mut env = TestEnv::new()?;
+        let file1 = env.create_test_file("file1.txt");
+        let file2 = env.create_test_file("file2.txt");
+        let dir = env.create_test_dir("dir");
+        let file_in_dir = env.create_test_file("dir/file3.txt");
+
+        env.manager
+            .track(&[file1.clone(), file2.clone(), dir.clone()])?;
+        let tracked = env.tracked_paths();
+        assert!(tracked.contains(&file1));
+        assert!(tracked.contains(&file2));
+        assert!(tracked.contains(&file_in_dir));
+
+        Ok(())
+    }
+
+    #[test]
+    fn untracking_non_tracked_file() -> Result<()> {
+        let mut env = TestEnv::new()?;
+        let file = env.create_test_file("file.txt");
+
+        // Attempt to untrack a file that isn't tracked
+        let result = env.manager.untrack(&[file.clone()]);
+        assert!(
+            result.is_ok(),
+            "Untracking non-tracked file should not fail"
+        );
+
+        // Verify that the file still exists and nothing was changed
+        assert!(file.exists());
+        assert!(env.tracked_paths().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn commit_with_no_staged_changes() -> Result<()> {
+        let env = TestEnv::new()?;
+
+        // Attempt to commit without staging any changes
+        let result = env.manager.save_local_changes();
+        assert!(result.is_err(), "Should error with no changes");
+        let err = result.unwrap_err();
+        assert!(
+            err.to_string().contains("No changes to commit"),
+            "Wrong error message"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn iterator_behavior_with_filters() -> Result<()> {
+        let mut env = TestEnv::new()?;
+        let file1 = env.create_test_file("file1.txt");
+        let file2 = env.create_test_file("file2.txt");
+
+        env.manager.track(&[file1.clone(), file2.clone()])?;
+
+        // Set filter to All
+        env.manager.set_filter(ListFilter::All);
+        let tracked_all: Vec<_> = env.tracked_paths();
+        assert_eq!(tracked_all.len(), 2);
+        assert!(tracked_all.contains(&file1));
+        assert!(tracked_all.contains(&file2));
+
+        // Modify file1
+        fs::write(&file1, "modified content")?;
+
+        // Set filter to Modified
+        env.manager.set_filter(ListFilter::Modified);
+        let tracked_modified: Vec<_> = env.tracked_paths();
+        assert_eq!(tracked_modified.len(), 1);
+        assert!(tracked_modified.contains(&file1));
+
+        Ok(())
+    }
+
+    /// Commits `content` for `name` on `branch`, branching off the current HEAD.
+    fn commit_on_branch(env: &TestEnv, branch: &str, name: &str, content: &str) -> Result<()> {
+        let repo = &env.manager.bare;
+        let head = repo.head()?.peel_to_commit()?;
+        let blob = repo.blob(content.as_bytes())?;
+        let mut builder = repo.treebuilder(Some(&head.tree()?))?;
+        builder.insert(name, blob, 0o100644)?;
+        let tree = repo.find_tree(builder.write()?)?;
+        let signature = repo.signature()?;
+        repo.commit(
+            Some(&format!("refs/heads/{branch}")),
+            &signature,
+            &signature,
+            "remote change",
+            &tree,
+            &[&head],
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn merge_without_overlap_creates_merge_commit() -> Result<()> {
+        let mut env = TestEnv::new()?;
+        let bashrc = env.create_test_file(".bashrc");
+        env.manager.track(std::slice::from_ref(&bashrc))?;
+        env.manager.save_local_changes()?;
+
+        commit_on_branch(&env, "remote", ".vimrc", "set number\n")?;
+        fs::write(&bashrc, "alias ll='ls -l'\n")?;
+        env.manager.track(&[bashrc])?;
+        env.manager.save_local_changes()?;
+
+        let outcome = env.manager.merge_reference("refs/heads/remote")?;
+        assert_eq!(outcome, MergeOutcome::Merged);
+        assert!(!env.manager.is_merging());
+        assert_eq!(env.manager.bare.head()?.peel_to_commit()?.parent_count(), 2);
+        assert!(env.workdir().join(".vimrc").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn conflicting_merge_waits_for_resolution() -> Result<()> {
+        let mut env = TestEnv::new()?;
+        let bashrc = env.create_test_file(".bashrc");
+        env.manager.track(std::slice::from_ref(&bashrc))?;
+        env.manager.save_local_changes()?;
+
+        commit_on_branch(&env, "remote", ".bashrc", "export EDITOR=nano\n")?;
+        fs::write(&bashrc, "export EDITOR=vim\n")?;
+        env.manager.track(std::slice::from_ref(&bashrc))?;
+        env.manager.save_local_changes()?;
+
+        let outcome = env.manager.merge_reference("refs/heads/remote")?;
+        assert_eq!(
+            outcome,
+            MergeOutcome::Conflicts(vec![PathBuf::from(".bashrc")])
+        );
+        assert!(env.manager.is_merging());
+        assert!(fs::read_to_string(&bashrc)?.contains(CONFLICT_MARKER));
+        assert!(env.manager.save_local_changes().is_err());
+        assert!(env.manager.finish_merge().is_err());
+
+        env.manager
+            .resolve_conflict(Path::new(".bashrc"), Resolution::Theirs)?;
+        assert_eq!(fs::read_to_string(&bashrc)?, "export EDITOR=nano\n");
+
+        env.manager.finish_merge()?;
+        assert!(!env.manager.is_merging());
+        assert_eq!(env.manager.bare.head()?.peel_to_commit()?.parent_count(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn oversized_files_are_refused_when_configured() -> Result<()> {
+        let mut env = TestEnv::new()?;
+        let small = env.create_test_file("small.txt");
+        let big = env.workdir().join("cache/big.bin");
+        fs::create_dir_all(big.parent().unwrap())?;
+        fs::write(&big, vec![0u8; 64])?;
+
+        env.manager.configure(DotsConfig {
+            max_file_size: 32,
+            refuse_oversized: true,
+            ..DotsConfig::default()
+        });
+
+        assert!(env.manager.track(std::slice::from_ref(&big)).is_err());
+        assert!(
+            env.manager
+                .track(&[big.parent().unwrap().to_path_buf()])
+                .is_err()
+        );
+        env.manager.track(std::slice::from_ref(&small))?;
+        assert_eq!(env.tracked_paths(), vec![small]);
+
+        env.manager.configure(DotsConfig {
+            max_file_size: 32,
+            refuse_oversized: false,
+            ..DotsConfig::default()
+        });
+        env.manager.track(std::slice::from_ref(&big))?;
+        assert!(env.tracked_paths().contains(&big));
+
+        Ok(())
+    }
+
+    #[test]
+    fn purge_removes_path_from_history() -> Result<()> {
+        let mut env = TestEnv::new()?;
+        let secret = env.create_test_file("cache/blob.bin");
+        fs::write(&secret, vec![0u8; 256])?;
+        let bashrc = env.create_test_file(".bashrc");
+        env.manager.track(&[secret.clone(), bashrc.clone()])?;
+        env.manager.save_local_changes()?;
+        fs::write(&bashrc, "export PS1='$ '\n")?;
+        env.manager.track(std::slice::from_ref(&bashrc))?;
+        env.manager.save_local_changes()?;
+
+        let usage = env.manager.disk_usage(10)?;
+        assert_eq!(usage.tracked.len(), 2);
+        assert_eq!(usage.tracked[0].1, 256);
+        assert_eq!(usage.history.len(), 3);
+
+        // Refs `pull` and tagging leave behind must not keep the blob alive.
+        let old_id = {
+            let bare = &env.manager.bare;
+            let head = bare.head()?.peel_to_commit()?;
+            bare.reference("refs/remotes/origin/main", head.id(), true, "test")?;
+            bare.tag_lightweight("v1", head.as_object(), false)?;
+            fs::write(bare.path().join("ORIG_HEAD"), format!("{}\n", head.id()))?;
+            head.tree()?.get_path(Path::new("cache/blob.bin"))?.id()
+        };
+
+        let rewritten = env.manager.purge(&env.workdir().join("cache"))?;
+        assert_eq!(rewritten, 2);
+        assert!(env.manager.bare.find_blob(old_id).is_err());
+        assert!(
+            env.manager
+                .bare
+                .find_reference("refs/remotes/origin/main")
+                .is_err()
+        );
+        let tagged = env
+            .manager
+            .bare
+            .revparse_single("v1")?
+            .peel_to_commit()?
+            .id();
+        assert_eq!(Some(tagged), env.manager.bare.head()?.target());
+
+        let mut revwalk = env.manager.bare.revwalk()?;
+        revwalk.push_head()?;
+        for oid in revwalk {
+            let tree = env.manager.bare.find_commit(oid?)?.tree()?;
+            assert!(tree.get_path(Path::new("cache/blob.bin")).is_err());
+            assert!(tree.get_path(Path::new(".bashrc")).is_ok());
+        }
+        assert_eq!(env.tracked_paths(), vec![bashrc]);
+        assert!(secret.exists(), "Purge must not touch the work tree");
+
+        Ok(())
+    }
+
+    #[test]
+    fn mutating_operations_fail_while_vault_is_locked() -> Result<()> {
+        let mut env = TestEnv::new()?;
+        let file = env.create_test_file("file.txt");
+
+        let held = VaultLock::acquire(env.manager.bare.path(), false)?;
+        let err = env.manager.track(std::slice::from_ref(&file)).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Shelfor>(),
+            Some(Shelfor::VaultLocked(..))
+        ));
+        assert!(env.tracked_paths().is_empty());
+
+        drop(held);
+        env.manager.track(std::slice::from_ref(&file))?;
+        env.manager.save_local_changes()?;
+
+        Ok(())
+    }
+
+    /// Configures a root named `etc` pointing at a fresh directory outside the work tree.
+    fn configure_outside_root(env: &mut TestEnv) -> Result<tempfile::TempDir> {
+        let outside = tempdir()?;
+        env.manager.configure(DotsConfig {
+            roots: vec![RootConfig {
+                name: "etc".to_string(),
+                path: outside.path().to_path_buf(),
+                sudo: false,
+            }],
+            ..DotsConfig::default()
+        });
+        Ok(outside)
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn files_under_roots_are_tracked_and_restored() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mut env = TestEnv::new()?;
+        let outside = configure_outside_root(&mut env)?;
+        let hosts = outside.path().join("hosts");
+        fs::write(&hosts, "127.0.0.1 localhost\n")?;
+        fs::set_permissions(&hosts, fs::Permissions::from_mode(0o600))?;
+
+        env.manager.track(std::slice::from_ref(&hosts))?;
+        assert_eq!(env.tracked_paths(), vec![hosts.clone()]);
+        env.manager.save_local_changes()?;
+
+        {
+            let tree = env.manager.bare.head()?.peel_to_tree()?;
+            assert!(tree.get_path(Path::new(".shelf-roots/etc/hosts")).is_ok());
+            assert!(
+                tree.get_path(Path::new(".shelf-roots/etc/.permissions"))
+                    .is_ok()
+            );
+        }
+
+        env.manager.set_filter(ListFilter::Modified);
+        assert!(env.tracked_paths().is_empty());
+        fs::write(&hosts, "127.0.0.1 example\n")?;
+        assert_eq!(env.tracked_paths(), vec![hosts.clone()]);
+
+        fs::remove_file(&hosts)?;
+        assert_eq!(env.manager.restore_roots(&[])?, vec![hosts.clone()]);
+        assert_eq!(fs::read_to_string(&hosts)?, "127.0.0.1 localhost\n");
+        assert_eq!(fs::metadata(&hosts)?.permissions().mode() & 0o777, 0o600);
+
+        env.manager.set_filter(ListFilter::All);
+        env.manager.untrack(std::slice::from_ref(&hosts))?;
+        assert!(env.tracked_paths().is_empty());
+        assert!(
+            env.manager
+                .get_index()?
+                .get_path(Path::new(".shelf-roots/etc/.permissions"), 0)
+                .is_none()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn fast_forward_keeps_roots_out_of_the_work_tree() -> Result<()> {
+        let mut env = TestEnv::new()?;
+        let _outside = configure_outside_root(&mut env)?;
+        let bashrc = env.create_test_file(".bashrc");
+        env.manager.track(std::slice::from_ref(&bashrc))?;
+        env.manager.save_local_changes()?;
+
+        let repo = &env.manager.bare;
+        let head = repo.head()?.peel_to_commit()?;
+        let mut index = Index::new()?;
+        index.read_tree(&head.tree()?)?;
+        let entry = |path: &str, content: &[u8]| -> Result<git2::IndexEntry> {
+            Ok(git2::IndexEntry {
+                ctime: git2::IndexTime::new(0, 0),
+                mtime: git2::IndexTime::new(0, 0),
+                dev: 0,
+                ino: 0,
+                mode: 0o100644,
+                uid: 0,
+                gid: 0,
+                file_size: 0,
+                id: repo.blob(content)?,
+                flags: 0,
+                flags_extended: 0,
+                path: path.as_bytes().to_vec(),
+            })
+        };
+        index.add(&entry(".bashrc", b"export EDITOR=nvim\n")?)?;
+        index.add(&entry(".shelf-roots/etc/hosts", b"10.0.0.1 nas\n")?)?;
+        let tree = repo.find_tree(index.write_tree_to(repo)?)?;
+        let signature = repo.signature()?;
+        repo.commit(
+            Some("refs/heads/remote"),
+            &signature,
+            &signature,
+            "remote change",
+            &tree,
+            &[&head],
+        )?;
+
+        let outcome = env.manager.merge_reference("refs/heads/remote")?;
+        assert_eq!(outcome, MergeOutcome::FastForward);
+        assert_eq!(fs::read_to_string(&bashrc)?, "export EDITOR=nvim\n");
+        assert_eq!(
+            env.manager.bare.status_file(Path::new(".bashrc"))?,
+            git2::Status::CURRENT
+        );
+        assert!(!env.workdir().join(".shelf-roots").exists());
+        assert!(
+            env.manager
+                .get_index()?
+                .get_path(Path::new(".shelf-roots/etc/hosts"), 0)
+                .is_some()
+        );
+        assert!(
+            env.manager
+                .verify_staged_changes(&env.manager.repository_status()?)
+                .is_err()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn dry_run_previews_without_touching_the_vault() -> Result<()> {
+        let mut env = TestEnv::new()?;
+        let dir = env.create_test_dir("config/nvim");
+        let init = env.create_test_file("config/nvim/init.lua");
+        let plugins = env.create_test_file("config/nvim/lua/plugins.lua");
+
+        env.manager.set_dry_run(true);
+        let changes = env.manager.track(std::slice::from_ref(&dir))?;
+        assert_eq!(changes.added, vec![init.clone(), plugins.clone()]);
+        assert!(changes.updated.is_empty() && changes.removed.is_empty());
+        assert!(env.tracked_paths().is_empty());
+
+        env.manager.set_dry_run(false);
+        env.manager.track(std::slice::from_ref(&dir))?;
+        env.manager.save_local_changes()?;
+        let head = env.manager.bare.head()?.target();
+
+        env.manager.set_dry_run(true);
+        let changes = env.manager.untrack(std::slice::from_ref(&dir))?;
+        assert_eq!(changes.removed, vec![init.clone(), plugins.clone()]);
+        assert_eq!(env.tracked_paths().len(), 2);
+
+        fs::write(&init, "vim.o.number = true\n")?;
+        env.manager.set_dry_run(false);
+        env.manager.track(std::slice::from_ref(&init))?;
+        env.manager.set_dry_run(true);
+        let message = env.manager.save_local_changes()?;
+        assert!(message.contains("config/nvim/init.lua"));
+        assert_eq!(env.manager.bare.head()?.target(), head);
+        assert_eq!(env.manager.staged_paths()?, vec![init]);
+        assert!(env.manager.pull("origin", None).is_err());
+
+        Ok(())
+    }
+
+    /// Number of files in the vault's object database.
+    fn object_count(env: &TestEnv) -> usize {
+        walkdir::WalkDir::new(env.manager.bare.path().join("objects"))
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_file())
+            .count()
+    }
+
+    #[test]
+    fn dry_runs_write_no_objects() -> Result<()> {
+        let mut env = TestEnv::new()?;
+        let dir = env.create_test_dir("config/nvim");
+        let init = env.create_test_file("config/nvim/init.lua");
+        env.manager.track(std::slice::from_ref(&init))?;
+        env.manager.save_local_changes()?;
+        fs::write(&init, "vim.o.number = true\n")?;
+        env.create_test_file("config/nvim/lua/plugins.lua");
+        let rc = env.create_test_file(".zshrc");
+        fs::write(&rc, "setopt autocd\n")?;
+        let before = object_count(&env);
+
+        env.manager.set_dry_run(true);
+        let changes = env.manager.track(&[dir, rc])?;
+        assert_eq!(changes.added.len(), 2);
+        assert_eq!(changes.updated, vec![init]);
+        assert_eq!(env.manager.purge(&env.workdir().join("config"))?, 1);
+        assert_eq!(object_count(&env), before);
+
+        Ok(())
+    }
+}
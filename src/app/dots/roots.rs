@@ -0,0 +1,309 @@
+use anyhow::{Context, Result, anyhow};
+use dialoguer::{Confirm, theme::ColorfulTheme};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+use tempfile::NamedTempFile;
+
+use crate::config::RootConfig;
+
+/// Directory inside the vault under which every root gets its own namespace.
+pub(super) const ROOTS_NAMESPACE: &str = ".shelf-roots";
+/// Per-root file recording the mode and ownership of every tracked file.
+pub(super) const PERMISSIONS_FILE: &str = ".permissions";
+
+const DEFAULT_FILE_MODE: u32 = 0o644;
+
+/// Mode and ownership of a file tracked under a root.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct FilePermissions {
+    pub(super) mode: u32,
+    pub(super) uid: u32,
+    pub(super) gid: u32,
+}
+
+/// Finds the root with the longest prefix containing `path` and returns it
+/// together with `path` relative to that prefix.
+pub(super) fn find_root<'a>(
+    roots: &'a [RootConfig],
+    path: &Path,
+) -> Option<(&'a RootConfig, PathBuf)> {
+    roots
+        .iter()
+        .filter_map(|root| {
+            path.strip_prefix(&root.path)
+                .ok()
+                .map(|relative| (root, relative.to_path_buf()))
+        })
+        .max_by_key(|(root, _)| root.path.components().count())
+}
+
+/// Maps a path relative to `root` to its location inside the vault.
+pub(super) fn vault_path(root: &RootConfig, relative: &Path) -> Result<PathBuf> {
+    let mut components = Path::new(&root.name).components();
+    if !matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    ) {
+        return Err(anyhow!(
+            "Root name {:?} must be a single path component",
+            root.name
+        ));
+    }
+    Ok(Path::new(ROOTS_NAMESPACE).join(&root.name).join(relative))
+}
+
+/// Reverses [`vault_path`], returning the root and the path relative to it.
+pub(super) fn from_vault_path<'a>(
+    roots: &'a [RootConfig],
+    vault_relative: &Path,
+) -> Option<(&'a RootConfig, PathBuf)> {
+    let rest = vault_relative.strip_prefix(ROOTS_NAMESPACE).ok()?;
+    let mut components = rest.components();
+    let name = components.next()?.as_os_str().to_str()?;
+    let root = roots.iter().find(|root| root.name == name)?;
+    Some((root, components.as_path().to_path_buf()))
+}
+
+/// Location of the permissions record for `root` inside the vault.
+pub(super) fn permissions_path(root: &RootConfig) -> PathBuf {
+    Path::new(ROOTS_NAMESPACE)
+        .join(&root.name)
+        .join(PERMISSIONS_FILE)
+}
+
+/// Returns `true` for the permissions record of any root, given an index entry path.
+pub(super) fn is_permissions_record(vault_relative: &[u8]) -> bool {
+    let Ok(path) = std::str::from_utf8(vault_relative) else {
+        return false;
+    };
+    let path = Path::new(path);
+    path.file_name()
+        .is_some_and(|name| name == PERMISSIONS_FILE)
+        && path
+            .parent()
+            .and_then(Path::parent)
+            .is_some_and(|namespace| namespace == Path::new(ROOTS_NAMESPACE))
+}
+
+/// Parses a permissions record: one `<octal mode> <uid> <gid> <path>` line per file.
+pub(super) fn parse_permissions(text: &str) -> BTreeMap<PathBuf, FilePermissions> {
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(4, ' ');
+            let mode = u32::from_str_radix(fields.next()?, 8).ok()?;
+            let uid = fields.next()?.parse().ok()?;
+            let gid = fields.next()?.parse().ok()?;
+            let path = PathBuf::from(fields.next()?);
+            Some((path, FilePermissions { mode, uid, gid }))
+        })
+        .collect()
+}
+
+/// Serializes a permissions record in the format read by [`parse_permissions`].
+pub(super) fn format_permissions(permissions: &BTreeMap<PathBuf, FilePermissions>) -> String {
+    permissions
+        .iter()
+        .map(|(path, perms)| {
+            format!(
+                "{:o} {} {} {}\n",
+                perms.mode,
+                perms.uid,
+                perms.gid,
+                path.display()
+            )
+        })
+        .collect()
+}
+
+/// Reads the mode and ownership of `path` without following symlinks.
+#[cfg(unix)]
+pub(super) fn read_permissions(path: &Path) -> Result<FilePermissions> {
+    use std::os::unix::fs::MetadataExt;
+    let meta = fs::symlink_metadata(path)?;
+    Ok(FilePermissions {
+        mode: meta.mode() & 0o7777,
+        uid: meta.uid(),
+        gid: meta.gid(),
+    })
+}
+
+#[cfg(not(unix))]
+pub(super) fn read_permissions(path: &Path) -> Result<FilePermissions> {
+    fs::symlink_metadata(path)?;
+    Ok(FilePermissions {
+        mode: DEFAULT_FILE_MODE,
+        uid: 0,
+        gid: 0,
+    })
+}
+
+/// Reads a file under `root`, escalating with `sudo` when permitted and needed.
+pub(super) fn read_file(root: &RootConfig, path: &Path) -> Result<Vec<u8>> {
+    match fs::read(path) {
+        Ok(content) => Ok(content),
+        Err(e) if e.kind() == ErrorKind::PermissionDenied && root.sudo => {
+            confirm_escalation(&format!("Read {} with sudo?", path.display()))?;
+            let output = Command::new("sudo")
+                .arg("cat")
+                .arg(path)
+                .stdin(Stdio::inherit())
+                .stderr(Stdio::inherit())
+                .output()
+                .context("Failed to run sudo")?;
+            if !output.status.success() {
+                return Err(anyhow!("sudo failed to read {}", path.display()));
+            }
+            Ok(output.stdout)
+        }
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+/// Writes `content` to `path` with the recorded permissions, escalating with
+/// `sudo` when permitted and needed.
+pub(super) fn write_file(
+    root: &RootConfig,
+    path: &Path,
+    content: &[u8],
+    permissions: Option<FilePermissions>,
+) -> Result<()> {
+    let mode = permissions.map_or(DEFAULT_FILE_MODE, |perms| perms.mode);
+    match write_direct(path, content, mode) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::PermissionDenied && root.sudo => {
+            confirm_escalation(&format!("Write {} with sudo?", path.display()))?;
+            write_with_sudo(path, content, mode, permissions)
+        }
+        Err(e) => Err(e).with_context(|| format!("Failed to write {}", path.display())),
+    }
+}
+
+fn write_direct(path: &Path, content: &[u8], mode: u32) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, content)?;
+    set_mode(path, mode)
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: u32) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// Installs the file through `sudo install`, which also restores ownership.
+fn write_with_sudo(
+    path: &Path,
+    content: &[u8],
+    mode: u32,
+    permissions: Option<FilePermissions>,
+) -> Result<()> {
+    let mut staged = NamedTempFile::new().context("Failed to create temporary file")?;
+    staged.write_all(content)?;
+
+    let mut install = Command::new("sudo");
+    install
+        .arg("install")
+        .arg("-D")
+        .arg("-m")
+        .arg(format!("{mode:o}"));
+    if let Some(perms) = permissions {
+        install
+            .arg("-o")
+            .arg(perms.uid.to_string())
+            .arg("-g")
+            .arg(perms.gid.to_string());
+    }
+    let status = install
+        .arg(staged.path())
+        .arg(path)
+        .status()
+        .context("Failed to run sudo")?;
+
+    if !status.success() {
+        return Err(anyhow!("sudo failed to write {}", path.display()));
+    }
+    Ok(())
+}
+
+fn confirm_escalation(prompt: &str) -> Result<()> {
+    let confirmed = Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .default(false)
+        .interact()?;
+    confirmed
+        .then_some(())
+        .ok_or_else(|| anyhow!("Escalation declined"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn root(name: &str, path: &str) -> RootConfig {
+        RootConfig {
+            name: name.to_string(),
+            path: PathBuf::from(path),
+            sudo: false,
+        }
+    }
+
+    #[test]
+    fn paths_round_trip_through_the_longest_matching_root() -> Result<()> {
+        let roots = [root("etc", "/etc"), root("nixos", "/etc/nixos")];
+
+        let (found, relative) = find_root(&roots, Path::new("/etc/nixos/configuration.nix"))
+            .expect("path should be under a root");
+        assert_eq!(found.name, "nixos");
+        assert_eq!(relative, PathBuf::from("configuration.nix"));
+
+        let stored = vault_path(found, &relative)?;
+        assert_eq!(
+            stored,
+            PathBuf::from(".shelf-roots/nixos/configuration.nix")
+        );
+        let (back, back_relative) = from_vault_path(&roots, &stored).expect("known namespace");
+        assert_eq!(
+            back.path.join(back_relative),
+            PathBuf::from("/etc/nixos/configuration.nix")
+        );
+
+        assert!(find_root(&roots, Path::new("/usr/local/etc/foo")).is_none());
+        assert!(vault_path(&root("../escape", "/opt"), Path::new("x")).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn permissions_record_round_trips() {
+        let mut permissions = BTreeMap::new();
+        permissions.insert(
+            PathBuf::from("ssh/sshd_config"),
+            FilePermissions {
+                mode: 0o600,
+                uid: 0,
+                gid: 0,
+            },
+        );
+        permissions.insert(
+            PathBuf::from("hosts with space"),
+            FilePermissions {
+                mode: 0o644,
+                uid: 0,
+                gid: 10,
+            },
+        );
+
+        let text = format_permissions(&permissions);
+        assert_eq!(parse_permissions(&text), permissions);
+    }
+}
@@ -1,7 +1,9 @@
 pub(super) mod dots;
 
+mod changelog;
 mod commit;
 mod completion;
+mod pr;
 mod prompt;
 mod review;
 
@@ -24,6 +26,10 @@ pub enum Claps {
     Dots(dots::DotsCMD),
     /// Generate a commit message using AI or manage git hooks.
     Commit(commit::CommitCMD),
+    /// Generate a changelog section from a range of commits.
+    Changelog(changelog::ChangelogCMD),
+    /// Describe the current branch for a pull request.
+    Pr(pr::PrCMD),
     /// Review code changes and suggest improvements using AI.
     Review(review::ReviewCMD),
     /// Text based prompt and repository context.
@@ -36,6 +42,8 @@ pub async fn run_app(cli: Shelf, repo: Dots) -> Result<()> {
     match cli.command {
         Claps::Dots(args) => dots::run(args, repo).await?,
         Claps::Commit(args) => commit::run(args).await?,
+        Claps::Changelog(args) => changelog::run(args).await?,
+        Claps::Pr(args) => pr::run(args).await?,
         Claps::Review(args) => review::run(args).await?,
         Claps::Prompt(args) => prompt::run(args).await?,
         Claps::Completion(args) => completion::run(args).await?,
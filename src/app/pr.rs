@@ -0,0 +1,181 @@
+use anyhow::{Context, Result, anyhow};
+use clap::{Args, Subcommand};
+use handlebars::Handlebars;
+use rig::{client::builder::DynClientBuilder, completion::Prompt};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::json;
+use std::path::{Path, PathBuf};
+
+use super::commit::budget;
+use super::commit::ignore::IgnoreFilter;
+use super::commit::{load_template_with_fallback, response_text, strip_code_fence};
+use crate::config::find_and_load_config;
+use crate::git::{
+    ChangeSource, collect_file_changes, merge_base_range, range_commits, work_tree_root,
+};
+use crate::utils::spin_progress;
+
+const DESCRIBE_TEMPLATE_PATH: &str = "assets/prompts/pr_describe.hbs";
+
+/// Where GitHub looks for a pull request template, in order.
+const PR_TEMPLATE_PATHS: [&str; 6] = [
+    ".github/pull_request_template.md",
+    ".github/PULL_REQUEST_TEMPLATE.md",
+    "pull_request_template.md",
+    "PULL_REQUEST_TEMPLATE.md",
+    "docs/pull_request_template.md",
+    "docs/PULL_REQUEST_TEMPLATE.md",
+];
+
+const AI_TEMPERATURE: f64 = 0.2;
+const AI_MAX_TOKENS: u64 = 2048;
+
+const PREAMBLE: &str = "You are a senior engineer writing pull request descriptions that help reviewers understand a change quickly.";
+
+#[derive(Args)]
+pub struct PrCMD {
+    #[command(subcommand)]
+    pub action: PrAction,
+}
+
+#[derive(Subcommand)]
+pub enum PrAction {
+    /// Generate a title and description for the current branch.
+    Describe(DescribeArgs),
+}
+
+#[derive(Args)]
+pub struct DescribeArgs {
+    /// Branch the pull request will merge into
+    #[arg(short, long, default_value = "main")]
+    pub base: String,
+    /// Write the description to a file instead of stdout
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+    /// AI model provider to use for generation
+    #[arg(short, long, default_value = "gemini")]
+    pub provider: String,
+    /// Specific model to use for generation
+    #[arg(short, long, default_value = "gemini-2.5-flash")]
+    pub model: String,
+}
+
+/// The model's pull request proposal.
+#[derive(Debug, Deserialize, JsonSchema)]
+struct PullRequest {
+    /// One-line title.
+    title: String,
+    /// Markdown description.
+    body: String,
+}
+
+pub(super) async fn run(args: PrCMD) -> Result<()> {
+    match args.action {
+        PrAction::Describe(args) => describe(args).await,
+    }
+}
+
+async fn describe(args: DescribeArgs) -> Result<()> {
+    let settings = find_and_load_config()?.commit;
+    let ignore = IgnoreFilter::new(&settings.ignore, &[])?;
+
+    let range = merge_base_range(&args.base)?;
+    let commits = range_commits(range)?;
+    if commits.is_empty() {
+        return Err(anyhow!("HEAD has no commits that are not on {}", args.base));
+    }
+    let commit_list = commits
+        .iter()
+        .rev()
+        .map(|(oid, message)| format!("• {}: {}", &oid.to_string()[..7], message.trim_end()))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let mut changes = collect_file_changes(ChangeSource::Range(range))
+        .context("Failed to retrieve the branch's changes")?;
+    changes.retain(|change| !ignore.is_ignored(&change.path));
+    let budgeted = budget::fit_to_budget(changes, settings.token_budget_for(&args.model));
+    let mut diff = budgeted.diff;
+    if !budgeted.overflow.is_empty() {
+        diff.push_str("\nFurther changed files, left out to fit the prompt:\n");
+        for change in &budgeted.overflow {
+            diff.push_str(&budget::stat_line(change, "omitted"));
+        }
+    }
+
+    let template = load_template_with_fallback(DESCRIBE_TEMPLATE_PATH)?;
+    let data = json!({
+        "PR_SCHEMA": serde_json::to_string_pretty(&schemars::schema_for!(PullRequest))?,
+        "PR_TEMPLATE": find_pr_template(&work_tree_root()?)?.unwrap_or_default(),
+        "COMMITS": commit_list,
+        "CODE_CHANGES": diff,
+    });
+    let prompt = Handlebars::new()
+        .render_template(&template, &data)
+        .context("Failed to render pull request prompt template")?;
+
+    let pull_request = request_description(&args, prompt).await?;
+    let description = format!(
+        "{}\n\n{}\n",
+        pull_request.title.trim(),
+        pull_request.body.trim()
+    );
+    match &args.output {
+        Some(path) => std::fs::write(path, description)
+            .with_context(|| format!("Failed to write {}", path.display())),
+        None => {
+            print!("{description}");
+            Ok(())
+        }
+    }
+}
+
+/// Reads the repository's pull request template, if it has one.
+fn find_pr_template(root: &Path) -> Result<Option<String>> {
+    for candidate in PR_TEMPLATE_PATHS {
+        let path = root.join(candidate);
+        if path.is_file() {
+            let content = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            return Ok(Some(content));
+        }
+    }
+    Ok(None)
+}
+
+async fn request_description(args: &DescribeArgs, prompt: String) -> Result<PullRequest> {
+    let client = DynClientBuilder::new();
+    let agent = client
+        .agent(&args.provider, &args.model)?
+        .preamble(PREAMBLE)
+        .temperature(AI_TEMPERATURE)
+        .max_tokens(AI_MAX_TOKENS)
+        .build();
+
+    let response =
+        spin_progress(|| async { agent.prompt(prompt).await.map_err(anyhow::Error::from) }).await?;
+    serde_json::from_str(strip_code_fence(&response_text(response)))
+        .context("The model did not return a valid pull request description")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn github_template_is_found_before_root_ones() -> Result<()> {
+        let dir = tempdir()?;
+        assert_eq!(find_pr_template(dir.path())?, None);
+
+        std::fs::write(dir.path().join("PULL_REQUEST_TEMPLATE.md"), "root")?;
+        std::fs::create_dir(dir.path().join(".github"))?;
+        std::fs::write(
+            dir.path().join(".github/pull_request_template.md"),
+            "## What\n",
+        )?;
+        assert_eq!(find_pr_template(dir.path())?.as_deref(), Some("## What\n"));
+        Ok(())
+    }
+}
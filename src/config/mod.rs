@@ -2,6 +2,7 @@ use anyhow::Result;
 use directories::BaseDirs;
 use serde::Deserialize;
 use std::{
+    collections::BTreeMap,
     fs,
     path::{Path, PathBuf},
 };
@@ -19,11 +20,258 @@ pub(super) struct PromptConfig {
     pub(crate) skip_files: Vec<String>,
 }
 
+/// Default size above which `dots track` complains about a file (10 MiB).
+const DEFAULT_MAX_TRACKED_FILE_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Configuration for the dotfiles vault, loaded from `shelf.toml`.
+#[derive(Deserialize, Debug, Clone)]
+pub(crate) struct DotsConfig {
+    /// Files larger than this many bytes trigger a warning when tracked.
+    #[serde(default = "default_max_tracked_file_size")]
+    pub(crate) max_file_size: u64,
+    /// Refuse to track oversized files instead of only warning.
+    #[serde(default)]
+    pub(crate) refuse_oversized: bool,
+    /// Extra locations outside the home directory that may be tracked.
+    #[serde(default)]
+    pub(crate) roots: Vec<RootConfig>,
+}
+
+impl Default for DotsConfig {
+    fn default() -> Self {
+        Self {
+            max_file_size: DEFAULT_MAX_TRACKED_FILE_SIZE,
+            refuse_oversized: false,
+            roots: Vec::new(),
+        }
+    }
+}
+
+/// An absolute prefix outside the home directory mapped to a namespace in the vault.
+#[derive(Deserialize, Debug, Clone)]
+pub(crate) struct RootConfig {
+    /// Namespace inside the vault, a single path component such as `etc`.
+    pub(crate) name: String,
+    /// Absolute prefix on disk, such as `/etc`.
+    pub(crate) path: PathBuf,
+    /// Escalate with `sudo`, after a prompt, when the files can't be read or written directly.
+    #[serde(default)]
+    pub(crate) sudo: bool,
+}
+
+fn default_max_tracked_file_size() -> u64 {
+    DEFAULT_MAX_TRACKED_FILE_SIZE
+}
+
+/// Default number of tokens of staged changes sent to the model (~96 KiB of patch).
+const DEFAULT_DIFF_TOKEN_BUDGET: usize = 24_000;
+
+/// Configuration for `shelf commit`, loaded from `shelf.toml`.
+#[derive(Deserialize, Debug, Clone)]
+pub(crate) struct CommitSettings {
+    /// Enforce Conventional Commits on generated messages when present.
+    #[serde(default)]
+    pub(crate) conventional: Option<ConventionalConfig>,
+    /// Estimated tokens of staged changes to send to the model.
+    #[serde(default = "default_diff_token_budget")]
+    pub(crate) token_budget: usize,
+    /// Per-model overrides of `token_budget`, keyed by model name.
+    #[serde(default)]
+    pub(crate) model_token_budgets: BTreeMap<String, usize>,
+    /// Glob patterns for paths kept out of the staged diff and history sent to the model.
+    #[serde(default)]
+    pub(crate) ignore: Vec<String>,
+    /// Extract ticket IDs from the branch name when present.
+    #[serde(default)]
+    pub(crate) tickets: Option<TicketConfig>,
+    /// Trailers appended to every generated message.
+    #[serde(default)]
+    pub(crate) trailers: Vec<TrailerConfig>,
+    /// Learn the commit style from the repository's history and follow it.
+    #[serde(default = "default_learn_style")]
+    pub(crate) learn_style: bool,
+    /// Language to write messages in, such as `German`; detected from the
+    /// history when unset.
+    #[serde(default)]
+    pub(crate) language: Option<String>,
+    /// Rules checked by `shelf commit lint`.
+    #[serde(default)]
+    pub(crate) lint: LintConfig,
+}
+
+/// Rules `shelf commit lint` checks messages against.
+#[derive(Deserialize, Debug, Clone)]
+pub(crate) struct LintConfig {
+    /// Maximum length of the subject line.
+    #[serde(default = "default_lint_line_length")]
+    pub(crate) max_subject_length: usize,
+    /// Require a blank line between the subject and the body.
+    #[serde(default = "default_require_blank_line")]
+    pub(crate) require_blank_line: bool,
+    /// Column at which body lines must be wrapped.
+    #[serde(default = "default_lint_line_length")]
+    pub(crate) max_body_line_length: usize,
+    /// Words and phrases that may not appear, matched case-insensitively.
+    #[serde(default)]
+    pub(crate) forbidden_words: Vec<String>,
+    /// Check the Conventional Commits format, with the `[commit.conventional]`
+    /// rules when present and the defaults otherwise.
+    #[serde(default)]
+    pub(crate) conventional: bool,
+    /// Trailer tokens every message must carry, such as `Signed-off-by`.
+    #[serde(default)]
+    pub(crate) required_trailers: Vec<String>,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        Self {
+            max_subject_length: default_lint_line_length(),
+            require_blank_line: default_require_blank_line(),
+            max_body_line_length: default_lint_line_length(),
+            forbidden_words: Vec::new(),
+            conventional: false,
+            required_trailers: Vec::new(),
+        }
+    }
+}
+
+/// The prompt asks for lines of at most 80 characters.
+fn default_lint_line_length() -> usize {
+    80
+}
+
+fn default_require_blank_line() -> bool {
+    true
+}
+
+/// How ticket IDs are found in branch names and recorded in messages.
+#[derive(Deserialize, Debug, Clone)]
+pub(crate) struct TicketConfig {
+    /// Regexes matched against the branch name; the first capture group, or
+    /// the whole match without groups, is the ticket ID.
+    #[serde(default = "default_ticket_patterns")]
+    pub(crate) patterns: Vec<String>,
+    /// Trailer token used to reference the tickets, such as `Refs` or `Closes`.
+    #[serde(default = "default_ticket_trailer")]
+    pub(crate) trailer: String,
+}
+
+/// A trailer such as `Co-authored-by: Name <email>`.
+#[derive(Deserialize, Debug, Clone)]
+pub(crate) struct TrailerConfig {
+    pub(crate) token: String,
+    pub(crate) value: String,
+}
+
+fn default_learn_style() -> bool {
+    true
+}
+
+fn default_ticket_patterns() -> Vec<String> {
+    vec![r"\b([A-Z][A-Z0-9]+-[0-9]+)\b".to_string()]
+}
+
+fn default_ticket_trailer() -> String {
+    "Refs".to_string()
+}
+
+impl Default for CommitSettings {
+    fn default() -> Self {
+        Self {
+            conventional: None,
+            token_budget: DEFAULT_DIFF_TOKEN_BUDGET,
+            model_token_budgets: BTreeMap::new(),
+            ignore: Vec::new(),
+            tickets: None,
+            trailers: Vec::new(),
+            learn_style: default_learn_style(),
+            language: None,
+            lint: LintConfig::default(),
+        }
+    }
+}
+
+impl CommitSettings {
+    /// Token budget for the staged changes sent to `model`.
+    pub(crate) fn token_budget_for(&self, model: &str) -> usize {
+        self.model_token_budgets
+            .get(model)
+            .copied()
+            .unwrap_or(self.token_budget)
+    }
+}
+
+fn default_diff_token_budget() -> usize {
+    DEFAULT_DIFF_TOKEN_BUDGET
+}
+
+/// Rules generated messages must follow to be valid Conventional Commits.
+#[derive(Deserialize, Debug, Clone)]
+pub(crate) struct ConventionalConfig {
+    /// Allowed commit types.
+    #[serde(default = "default_conventional_types")]
+    pub(crate) types: Vec<String>,
+    /// Allowed scopes; any scope is accepted when empty.
+    #[serde(default)]
+    pub(crate) scopes: Vec<String>,
+    /// Reject headers without a scope.
+    #[serde(default)]
+    pub(crate) require_scope: bool,
+    /// Maximum length of the header line.
+    #[serde(default = "default_max_header_length")]
+    pub(crate) max_header_length: usize,
+    /// Column at which body lines must be wrapped.
+    #[serde(default = "default_max_body_line_length")]
+    pub(crate) max_body_line_length: usize,
+    /// How many times to ask the model again when a message can't be repaired.
+    #[serde(default = "default_max_regenerations")]
+    pub(crate) max_regenerations: usize,
+}
+
+impl Default for ConventionalConfig {
+    fn default() -> Self {
+        Self {
+            types: default_conventional_types(),
+            scopes: Vec::new(),
+            require_scope: false,
+            max_header_length: default_max_header_length(),
+            max_body_line_length: default_max_body_line_length(),
+            max_regenerations: default_max_regenerations(),
+        }
+    }
+}
+
+fn default_conventional_types() -> Vec<String> {
+    [
+        "build", "chore", "ci", "docs", "feat", "fix", "perf", "refactor", "revert", "style",
+        "test",
+    ]
+    .map(String::from)
+    .to_vec()
+}
+
+fn default_max_header_length() -> usize {
+    72
+}
+
+fn default_max_body_line_length() -> usize {
+    72
+}
+
+fn default_max_regenerations() -> usize {
+    2
+}
+
 /// Main configuration structure, mirroring `shelf.toml`.
 #[derive(Deserialize, Default, Debug, Clone)]
 pub(super) struct Config {
     #[serde(default)]
     pub(crate) prompt: PromptConfig,
+    #[serde(default)]
+    pub(crate) dots: DotsConfig,
+    #[serde(default)]
+    pub(crate) commit: CommitSettings,
 }
 
 pub fn init_bare_repo() -> Result<Dots> {
@@ -115,7 +363,8 @@ fn try_load_from(path: &Path) -> Option<Result<Config>> {
         return None;
     }
 
-    println!("Loading config from: {}", path.display());
+    // Diagnostics go to stderr so commands that print machine-readable output stay clean.
+    eprintln!("Loading config from: {}", path.display());
     let result = fs::read_to_string(path)
         .map_err(anyhow::Error::from)
         .and_then(|content| toml::from_str(&content).map_err(anyhow::Error::from));
@@ -203,6 +452,103 @@ skip_files = ["README.md"]
         let cfg = result.unwrap().expect("expected Ok(Config)");
         assert_eq!(cfg.prompt.skip_directories, vec!["target", "node_modules"]);
         assert_eq!(cfg.prompt.skip_files, vec!["README.md"]);
+        assert_eq!(cfg.dots.max_file_size, DEFAULT_MAX_TRACKED_FILE_SIZE);
+        assert!(!cfg.dots.refuse_oversized);
+    }
+
+    #[test]
+    fn try_load_from_parses_conventional_rules() {
+        let _guard = lock_env();
+        let dir = make_temp_dir("shelf_test_conventional");
+        let p = dir.join("shelf.toml");
+        let toml = r#"
+[commit.conventional]
+types = ["feat", "fix"]
+scopes = ["cli", "dots"]
+"#;
+        write_file(&p, toml);
+        let cfg = try_load_from(&p)
+            .expect("expected Some(Result), got None")
+            .expect("expected Ok(Config)");
+        let rules = cfg
+            .commit
+            .conventional
+            .expect("expected conventional rules");
+        assert_eq!(rules.types, vec!["feat", "fix"]);
+        assert_eq!(rules.scopes, vec!["cli", "dots"]);
+        assert_eq!(rules.max_header_length, 72);
+        assert!(!rules.require_scope);
+    }
+
+    #[test]
+    fn try_load_from_parses_tickets_and_trailers() {
+        let _guard = lock_env();
+        let dir = make_temp_dir("shelf_test_trailers");
+        let p = dir.join("shelf.toml");
+        let toml = r#"
+[commit.tickets]
+
+[[commit.trailers]]
+token = "Co-authored-by"
+value = "Jane Doe <jane@example.com>"
+"#;
+        write_file(&p, toml);
+        let cfg = try_load_from(&p)
+            .expect("expected Some(Result), got None")
+            .expect("expected Ok(Config)");
+        let tickets = cfg.commit.tickets.expect("expected ticket settings");
+        assert_eq!(tickets.patterns, default_ticket_patterns());
+        assert_eq!(tickets.trailer, "Refs");
+        assert_eq!(cfg.commit.trailers.len(), 1);
+        assert_eq!(cfg.commit.trailers[0].token, "Co-authored-by");
+    }
+
+    #[test]
+    fn commit_token_budget_can_be_overridden_per_model() {
+        let _guard = lock_env();
+        let dir = make_temp_dir("shelf_test_budget");
+        let p = dir.join("shelf.toml");
+        let toml = r#"
+[commit]
+token_budget = 8000
+ignore = ["*.lock"]
+
+[commit.model_token_budgets]
+"gemini-2.5-pro" = 500000
+"#;
+        write_file(&p, toml);
+        let cfg = try_load_from(&p)
+            .expect("expected Some(Result), got None")
+            .expect("expected Ok(Config)");
+        assert_eq!(cfg.commit.token_budget_for("gemini-2.5-pro"), 500_000);
+        assert_eq!(cfg.commit.token_budget_for("gemini-2.5-flash-lite"), 8000);
+        assert!(cfg.commit.conventional.is_none());
+        assert_eq!(cfg.commit.ignore, vec!["*.lock"]);
+    }
+
+    #[test]
+    fn try_load_from_parses_dots_roots() {
+        let _guard = lock_env();
+        let dir = make_temp_dir("shelf_test_roots");
+        let p = dir.join("shelf.toml");
+        let toml = r#"
+[dots]
+refuse_oversized = true
+
+[[dots.roots]]
+name = "etc"
+path = "/etc"
+sudo = true
+"#;
+        write_file(&p, toml);
+        let cfg = try_load_from(&p)
+            .expect("expected Some(Result), got None")
+            .expect("expected Ok(Config)");
+        assert!(cfg.dots.refuse_oversized);
+        assert_eq!(cfg.dots.roots.len(), 1);
+        assert_eq!(cfg.dots.roots[0].name, "etc");
+        assert_eq!(cfg.dots.roots[0].path, PathBuf::from("/etc"));
+        assert!(cfg.dots.roots[0].sudo);
     }
 
     #[test]
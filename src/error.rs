@@ -19,4 +19,10 @@ pub enum Shelfor {
     StripPrefix(#[from] path::StripPrefixError),
     #[error("Git executable is not installed")]
     GitNotInstalled,
+    #[error("Vault is locked by process {0} ({1:?}), retry with --wait")]
+    VaultLocked(u32, PathBuf),
+    #[error("The {0} hook failed, commit aborted (use --no-verify to bypass)")]
+    HookFailed(&'static str),
+    #[error("Cannot commit while {0} is in progress; {1}")]
+    OperationInProgress(&'static str, &'static str),
 }
@@ -0,0 +1,1613 @@
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, Result, anyhow};
+use colored::Colorize;
+use git2::{Commit, DiffOptions, Oid, Repository, Tree};
+
+use crate::error::Shelfor;
+use signing::CommitSigner;
+
+mod signing;
+
+/// Helper function to determine the parent commits for a new commit.
+///
+/// Returns an empty vector if there's no HEAD (e.g., the first commit in a repo
+/// or an unborn branch). Otherwise, returns a vector containing the commit
+/// pointed to by HEAD. Propagates other `git2` errors.
+fn get_parent_commits<'repo>(repo: &'repo Repository) -> Result<Vec<Commit<'repo>>> {
+    match repo.head() {
+        Ok(head) => Ok(vec![head.peel_to_commit()?]),
+        Err(ref e) if e.code() == git2::ErrorCode::UnbornBranch => {
+            // This is the initial commit for an unborn branch; no parents
+            Ok(vec![])
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Creates a new commit with the given message, including all staged changes.
+///
+/// Unless `verify` is `false`, the repository's `pre-commit` and `commit-msg`
+/// hooks run first and may abort the commit; `commit-msg` may also rewrite the
+/// message. `post-commit` always runs afterwards and, as in git, cannot undo
+/// the commit.
+pub(super) fn commit_action(message: String, verify: bool) -> Result<String> {
+    commit_index(message, verify, false)
+}
+
+/// Replaces HEAD with a commit of the staged changes and the given message,
+/// keeping HEAD's author and parents. Hooks run as in [`commit_action`].
+pub(crate) fn amend_action(message: String, verify: bool) -> Result<String> {
+    commit_index(message, verify, true)
+}
+
+fn commit_index(message: String, verify: bool, amend: bool) -> Result<String> {
+    let repo = Repository::open(".").context("Failed to open local git repository")?;
+    commit_index_in(&repo, message, verify, amend)
+}
+
+fn commit_index_in(
+    repo: &Repository,
+    message: String,
+    verify: bool,
+    amend: bool,
+) -> Result<String> {
+    let signature = repo
+        .signature()
+        .context("Failed to determine git signature")?;
+    let operation = pending_operation_in(repo)?;
+    if amend && matches!(operation, Some(op) if op != PendingOperation::Rebase) {
+        return Err(Shelfor::OperationInProgress(
+            operation.map_or("", PendingOperation::name),
+            "conclude it with a new commit instead of amending",
+        )
+        .into());
+    }
+
+    let message = if verify {
+        run_hook(repo, "pre-commit", &[])?;
+        filter_through_commit_msg(repo, &message)?
+    } else {
+        message
+    };
+
+    // Create a tree from the current index (staged changes). The index is read
+    // only now because `pre-commit` hooks may have restaged files.
+    let mut index = repo.index().context("Failed to get repository index")?;
+    index
+        .read(true)
+        .context("Failed to reload repository index")?;
+    let tree_id = index
+        .write_tree()
+        .context("Failed to write index to tree")?;
+    let tree = repo
+        .find_tree(tree_id)
+        .context("Failed to find tree from index")?;
+
+    let signer = CommitSigner::from_config(repo)?;
+    let subject = message.lines().next().unwrap_or_default();
+
+    if amend {
+        let head = repo
+            .head()
+            .and_then(|head| head.peel_to_commit())
+            .context("There is no commit to amend")?;
+        let parents: Vec<Commit<'_>> = head.parents().collect();
+        let parent_references: Vec<&Commit<'_>> = parents.iter().collect();
+
+        // Keep the original author, as `git commit --amend` does.
+        let oid = write_commit(
+            repo,
+            signer.as_ref(),
+            &head.author(),
+            &signature,
+            &message,
+            &tree,
+            &parent_references,
+        )?;
+        move_head(repo, oid, &format!("commit (amend): {subject}"))?;
+        println!("{}", "Amended git commit successfully".bright_green());
+    } else {
+        // Determine parent commits, handling the initial commit case, plus
+        // the heads being merged.
+        let parents = operation_parents(repo, operation)?;
+        let parent_references: Vec<&Commit<'_>> = parents.iter().collect();
+        let author = operation_author(repo, operation, &signature)?;
+
+        // Create the commit.
+        let oid = write_commit(
+            repo,
+            signer.as_ref(),
+            &author,            // Author
+            &signature,         // Committer
+            &message,           // Commit message
+            &tree,              // Tree of files
+            &parent_references, // Parent commits
+        )?;
+        let reflog = match operation {
+            Some(PendingOperation::Merge) => "commit (merge)",
+            Some(PendingOperation::CherryPick { .. }) => "commit (cherry-pick)",
+            _ => "commit",
+        };
+        move_head(repo, oid, &format!("{reflog}: {subject}"))?;
+        finish_operation(repo, operation)?;
+        println!("{}", "Created git commit successfully".bright_green());
+    }
+
+    if let Err(e) = run_hook(repo, "post-commit", &[]) {
+        eprintln!("{} {e}", "warning:".bright_yellow().bold());
+    }
+    Ok(message)
+}
+
+/// An operation in progress that the next commit concludes or continues.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PendingOperation {
+    Merge,
+    /// `sequence` when further commits wait for `git cherry-pick --continue`.
+    CherryPick {
+        sequence: bool,
+    },
+    /// `sequence` when further commits wait for `git revert --continue`.
+    Revert {
+        sequence: bool,
+    },
+    Rebase,
+}
+
+impl PendingOperation {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::Merge => "a merge",
+            Self::CherryPick { .. } => "a cherry-pick",
+            Self::Revert { .. } => "a revert",
+            Self::Rebase => "a rebase",
+        }
+    }
+
+    /// The pseudo-ref naming the commit the operation brings in.
+    fn head_ref(self) -> &'static str {
+        match self {
+            Self::Merge => "MERGE_HEAD",
+            Self::CherryPick { .. } => "CHERRY_PICK_HEAD",
+            Self::Revert { .. } => "REVERT_HEAD",
+            Self::Rebase => "REBASE_HEAD",
+        }
+    }
+
+    /// State files git removes once a commit concludes the operation. A
+    /// rebase cleans up after itself on `git rebase --continue`.
+    fn state_files(self) -> &'static [&'static str] {
+        match self {
+            Self::Merge => &["MERGE_HEAD", "MERGE_MODE", "MERGE_MSG"],
+            Self::CherryPick { .. } => &["CHERRY_PICK_HEAD", "MERGE_MSG"],
+            Self::Revert { .. } => &["REVERT_HEAD", "MERGE_MSG"],
+            Self::Rebase => &[],
+        }
+    }
+}
+
+/// The operation the repository is in the middle of, if any.
+///
+/// Fails for states shelf can't commit in, such as `git am`.
+pub(crate) fn pending_operation() -> Result<Option<PendingOperation>> {
+    let repo = Repository::open(".").context("Failed to open local git repository")?;
+    pending_operation_in(&repo)
+}
+
+/// Whether the index still has unmerged entries from a conflict.
+pub(crate) fn index_has_conflicts() -> Result<bool> {
+    let repo = Repository::open(".").context("Failed to open local git repository")?;
+    Ok(repo
+        .index()
+        .context("Failed to get repository index")?
+        .has_conflicts())
+}
+
+fn pending_operation_in(repo: &Repository) -> Result<Option<PendingOperation>> {
+    use git2::RepositoryState as State;
+    Ok(match repo.state() {
+        State::Clean | State::Bisect => None,
+        State::Merge => Some(PendingOperation::Merge),
+        State::CherryPick => Some(PendingOperation::CherryPick { sequence: false }),
+        State::CherryPickSequence => Some(PendingOperation::CherryPick { sequence: true }),
+        State::Revert => Some(PendingOperation::Revert { sequence: false }),
+        State::RevertSequence => Some(PendingOperation::Revert { sequence: true }),
+        State::Rebase | State::RebaseInteractive | State::RebaseMerge => {
+            Some(PendingOperation::Rebase)
+        }
+        State::ApplyMailbox | State::ApplyMailboxOrRebase => {
+            return Err(Shelfor::OperationInProgress(
+                "git am",
+                "finish it with `git am --continue` or `git am --abort`",
+            )
+            .into());
+        }
+    })
+}
+
+/// Parents of a commit concluding `operation`: HEAD, if born, then the heads
+/// being merged.
+fn operation_parents(
+    repo: &Repository,
+    operation: Option<PendingOperation>,
+) -> Result<Vec<Commit<'_>>> {
+    let mut parents = get_parent_commits(repo)?;
+    if operation == Some(PendingOperation::Merge) {
+        let heads = fs::read_to_string(repo.path().join("MERGE_HEAD"))
+            .context("Failed to read MERGE_HEAD")?;
+        for line in heads.lines().filter(|line| !line.trim().is_empty()) {
+            parents.push(repo.find_commit(Oid::from_str(line.trim())?)?);
+        }
+    }
+    Ok(parents)
+}
+
+/// Author of a commit concluding `operation`. A cherry-pick keeps the picked
+/// commit's author, as `git commit` does. During a rebase the committer is
+/// the author: only `git rebase --continue` reuses the replayed commit's, and
+/// commits made at an `edit` stop are new work.
+fn operation_author(
+    repo: &Repository,
+    operation: Option<PendingOperation>,
+    signature: &git2::Signature<'_>,
+) -> Result<git2::Signature<'static>> {
+    match operation {
+        Some(op @ PendingOperation::CherryPick { .. }) => Ok(repo
+            .find_commit(repo.refname_to_id(op.head_ref())?)?
+            .author()
+            .to_owned()),
+        _ => Ok(signature.to_owned()),
+    }
+}
+
+/// Removes the state files of the operation the commit concluded, and a
+/// `SQUASH_MSG` left by `git merge --squash`, as `git commit` does.
+fn finish_operation(repo: &Repository, operation: Option<PendingOperation>) -> Result<()> {
+    let files = operation.map_or(&["SQUASH_MSG"][..], PendingOperation::state_files);
+    for file in files {
+        match fs::remove_file(repo.path().join(file)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                return Err(e).with_context(|| format!("Failed to remove {file}"));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// The commits `operation` brings in, newest first: the merged branches'
+/// commits not yet on HEAD, or the commit being picked, reverted or replayed.
+pub(crate) fn operation_commits(
+    operation: PendingOperation,
+    limit: usize,
+) -> Result<Vec<(Oid, String)>> {
+    let repo = Repository::open(".").context("Failed to open local git repository")?;
+    if operation != PendingOperation::Merge {
+        let Ok(oid) = repo.refname_to_id(operation.head_ref()) else {
+            return Ok(Vec::new());
+        };
+        let commit = repo.find_commit(oid)?;
+        return Ok(vec![(
+            oid,
+            commit.message().unwrap_or_default().to_string(),
+        )]);
+    }
+
+    let head = repo.head()?.peel_to_commit()?.id();
+    let mut commits = Vec::new();
+    for parent in operation_parents(&repo, Some(operation))?.iter().skip(1) {
+        let base = repo.merge_base(head, parent.id()).ok();
+        commits.extend(range_commits(CommitRange {
+            base,
+            tip: parent.id(),
+        })?);
+    }
+    commits.truncate(limit);
+    Ok(commits)
+}
+
+/// Writes a commit object, signed when `signer` is given, without moving any reference.
+fn write_commit(
+    repo: &Repository,
+    signer: Option<&CommitSigner>,
+    author: &git2::Signature,
+    committer: &git2::Signature,
+    message: &str,
+    tree: &Tree,
+    parents: &[&Commit],
+) -> Result<Oid> {
+    let Some(signer) = signer else {
+        return Ok(repo.commit(None, author, committer, message, tree, parents)?);
+    };
+
+    let buffer = repo.commit_create_buffer(author, committer, message, tree, parents)?;
+    let payload = buffer
+        .as_str()
+        .ok_or_else(|| anyhow!("Commit buffer is not valid UTF-8"))?;
+    let signature = signer.sign(payload, committer)?;
+    Ok(repo.commit_signed(payload, &signature, None)?)
+}
+
+/// Points HEAD, or the branch it refers to, at `oid`.
+fn move_head(repo: &Repository, oid: Oid, log_message: &str) -> Result<()> {
+    let head = repo.find_reference("HEAD")?;
+    match head.symbolic_target() {
+        Some(branch) => {
+            repo.reference(branch, oid, true, log_message)?;
+        }
+        None => repo.set_head_detached(oid)?,
+    }
+    Ok(())
+}
+
+/// The commits after `base` up to `tip`, as given to `reword`, `squash` and
+/// `changelog`. Rewriting additionally needs them on HEAD's first-parent history.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct CommitRange {
+    /// The commit the range starts after, `None` when it starts at the root.
+    pub(crate) base: Option<Oid>,
+    /// The last commit in the range.
+    pub(crate) tip: Oid,
+}
+
+/// Resolves a single revision to the range holding just that commit.
+pub(crate) fn resolve_commit(rev: &str) -> Result<CommitRange> {
+    let repo = Repository::open(".").context("Failed to open local git repository")?;
+    let commit = repo
+        .revparse_single(rev)
+        .and_then(|object| object.peel_to_commit())
+        .with_context(|| format!("Unknown revision {rev:?}"))?;
+    Ok(CommitRange {
+        base: commit.parent_id(0).ok(),
+        tip: commit.id(),
+    })
+}
+
+/// Resolves `A..B`, or `A` as a shorthand for `A..HEAD`.
+pub(crate) fn resolve_range(spec: &str) -> Result<CommitRange> {
+    let repo = Repository::open(".").context("Failed to open local git repository")?;
+    let spec = if spec.contains("..") {
+        spec.to_string()
+    } else {
+        format!("{spec}..HEAD")
+    };
+    let revspec = repo
+        .revparse(&spec)
+        .with_context(|| format!("Unknown revision range {spec:?}"))?;
+    let peel = |object: Option<&git2::Object>| -> Result<Oid> {
+        let object = object.ok_or_else(|| anyhow!("Incomplete revision range {spec:?}"))?;
+        Ok(object.peel_to_commit()?.id())
+    };
+    Ok(CommitRange {
+        base: Some(peel(revspec.from())?),
+        tip: peel(revspec.to())?,
+    })
+}
+
+/// The commits on HEAD since it forked from `base`, from their merge base to
+/// HEAD, as a branch about to be merged into `base` would bring in.
+pub(crate) fn merge_base_range(base: &str) -> Result<CommitRange> {
+    let repo = Repository::open(".").context("Failed to open local git repository")?;
+    let base_commit = repo
+        .revparse_single(base)
+        .and_then(|object| object.peel_to_commit())
+        .with_context(|| format!("Unknown revision {base:?}"))?;
+    let head = repo
+        .head()
+        .and_then(|head| head.peel_to_commit())
+        .context("Failed to resolve HEAD")?;
+    let merge_base = repo
+        .merge_base(base_commit.id(), head.id())
+        .with_context(|| format!("HEAD has no common ancestor with {base:?}"))?;
+    Ok(CommitRange {
+        base: Some(merge_base),
+        tip: head.id(),
+    })
+}
+
+/// Non-merge commits reachable from the range's tip but not its base, newest
+/// first, as listed by `git log --no-merges A..B`.
+pub(crate) fn range_commits(range: CommitRange) -> Result<Vec<(Oid, String)>> {
+    let repo = Repository::open(".").context("Failed to open local git repository")?;
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME)?;
+    revwalk.push(range.tip)?;
+    if let Some(base) = range.base {
+        revwalk.hide(base)?;
+    }
+
+    let mut commits = Vec::new();
+    for oid in revwalk {
+        let commit = repo.find_commit(oid?)?;
+        if commit.parent_count() > 1 {
+            continue;
+        }
+        commits.push((
+            commit.id(),
+            commit.message().unwrap_or_default().to_string(),
+        ));
+    }
+    Ok(commits)
+}
+
+/// The committer time of `oid`.
+pub(crate) fn commit_time(oid: Oid) -> Result<git2::Time> {
+    let repo = Repository::open(".").context("Failed to open local git repository")?;
+    Ok(repo.find_commit(oid)?.time())
+}
+
+/// Messages of the commits in `range`, oldest first.
+pub(crate) fn range_messages(range: CommitRange) -> Result<Vec<String>> {
+    let repo = Repository::open(".").context("Failed to open local git repository")?;
+    let (commits, _) = split_first_parent_history(&repo, range)?;
+    Ok(commits
+        .iter()
+        .rev()
+        .map(|commit| commit.message().unwrap_or_default().to_string())
+        .collect())
+}
+
+/// Replaces the commits in `range` with a single commit carrying `message`
+/// and the tree of the range's tip, then replays every later commit on top.
+///
+/// Only messages and parents change, so the working tree and the index are
+/// untouched. With `verify`, the new message passes through `commit-msg`.
+pub(crate) fn rewrite_range(range: CommitRange, message: &str, verify: bool) -> Result<Oid> {
+    let repo = Repository::open(".").context("Failed to open local git repository")?;
+    let message = if verify {
+        filter_through_commit_msg(&repo, message)?
+    } else {
+        message.to_string()
+    };
+    replace_range(&repo, range, &message)
+}
+
+fn replace_range(repo: &Repository, range: CommitRange, message: &str) -> Result<Oid> {
+    let signature = repo
+        .signature()
+        .context("Failed to determine git signature")?;
+    let (commits, descendants) = split_first_parent_history(repo, range)?;
+    let signer = CommitSigner::from_config(repo)?;
+
+    // The combined commit keeps the author of the oldest commit it replaces.
+    let oldest = commits
+        .last()
+        .ok_or_else(|| anyhow!("The range is empty"))?;
+    let tip = repo.find_commit(range.tip)?;
+    let parents = match range.base {
+        Some(base) => vec![repo.find_commit(base)?],
+        None => Vec::new(),
+    };
+    let parent_references: Vec<&Commit<'_>> = parents.iter().collect();
+    let mut new_tip = write_commit(
+        repo,
+        signer.as_ref(),
+        &oldest.author(),
+        &signature,
+        message,
+        &tip.tree()?,
+        &parent_references,
+    )?;
+
+    for commit in descendants.iter().rev() {
+        let parent = repo.find_commit(new_tip)?;
+        new_tip = write_commit(
+            repo,
+            signer.as_ref(),
+            &commit.author(),
+            &commit.committer(),
+            commit.message_raw().unwrap_or_default(),
+            &commit.tree()?,
+            &[&parent],
+        )?;
+    }
+
+    move_head(repo, new_tip, "shelf: rewrite commit message")?;
+    Ok(new_tip)
+}
+
+/// Walks HEAD's first-parent history and splits it into the commits of
+/// `range` and the commits after it, both newest first.
+///
+/// Fails if the range isn't on that history or if any commit to rewrite is a merge.
+fn split_first_parent_history(
+    repo: &Repository,
+    range: CommitRange,
+) -> Result<(Vec<Commit<'_>>, Vec<Commit<'_>>)> {
+    let mut descendants = Vec::new();
+    let mut commits = Vec::new();
+    let mut current = Some(
+        repo.head()
+            .and_then(|head| head.peel_to_commit())
+            .context("HEAD does not point to a commit")?,
+    );
+    let mut in_range = false;
+    let mut reached_base = range.base.is_none();
+
+    while let Some(commit) = current {
+        if Some(commit.id()) == range.base {
+            reached_base = true;
+            break;
+        }
+        if commit.id() == range.tip {
+            in_range = true;
+        }
+        if commit.parent_count() > 1 {
+            return Err(anyhow!(
+                "Cannot rewrite history through merge commit {}",
+                &commit.id().to_string()[..7]
+            ));
+        }
+        current = commit.parents().next();
+        if in_range {
+            commits.push(commit);
+        } else {
+            descendants.push(commit);
+        }
+    }
+
+    if !in_range || !reached_base {
+        return Err(anyhow!(
+            "The commits to rewrite are not on the current branch"
+        ));
+    }
+    Ok((commits, descendants))
+}
+
+/// Passes `message` through the `commit-msg` hook via `COMMIT_EDITMSG` and
+/// returns the message as the hook left it.
+fn filter_through_commit_msg(repo: &Repository, message: &str) -> Result<String> {
+    let message = git2::message_prettify(message, None)?;
+    let message_file = repo.path().join("COMMIT_EDITMSG");
+    fs::write(&message_file, &message)
+        .with_context(|| format!("Failed to write {}", message_file.display()))?;
+
+    if !run_hook(repo, "commit-msg", &[message_file.as_os_str()])? {
+        return Ok(message);
+    }
+
+    let rewritten = fs::read_to_string(&message_file)
+        .with_context(|| format!("Failed to read {}", message_file.display()))?;
+    let rewritten = git2::message_prettify(rewritten, None)?;
+    if rewritten.trim().is_empty() {
+        return Err(anyhow!("Aborting commit due to empty commit message"));
+    }
+    Ok(rewritten)
+}
+
+/// Runs the hook `name` from the repository's hooks directory, if it is
+/// installed, with the environment git provides to commit hooks.
+///
+/// Returns whether the hook ran. A non-zero exit fails with
+/// [`Shelfor::HookFailed`].
+fn run_hook(repo: &Repository, name: &'static str, args: &[&OsStr]) -> Result<bool> {
+    let hook_path = hooks_dir(repo)?.join(name);
+    if !is_executable(&hook_path) {
+        return Ok(false);
+    }
+
+    let index_path = repo.path().join("index");
+    let status = hook_command(&hook_path)
+        .args(args)
+        .current_dir(repo.workdir().unwrap_or_else(|| repo.path()))
+        .env("GIT_INDEX_FILE", &index_path)
+        .env("GIT_EDITOR", ":")
+        .status()
+        .with_context(|| format!("Failed to run the {name} hook"))?;
+
+    if !status.success() {
+        return Err(Shelfor::HookFailed(name).into());
+    }
+    Ok(true)
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(unix)]
+fn hook_command(hook_path: &Path) -> Command {
+    Command::new(hook_path)
+}
+
+/// Hooks are shell scripts without an executable format Windows understands.
+#[cfg(not(unix))]
+fn hook_command(hook_path: &Path) -> Command {
+    let mut command = Command::new("sh");
+    command.arg(hook_path);
+    command
+}
+
+/// A hunk of the staged or unstaged changes, or a whole file change without
+/// hunks (binary files, mode changes), as offered to `shelf commit --split`
+/// and `shelf commit --patch`.
+#[derive(Debug, Clone)]
+pub(crate) struct Hunk {
+    /// Position in its diff, used to refer to the hunk in a split plan or selection.
+    pub(crate) id: usize,
+    pub(crate) path: PathBuf,
+    /// The `@@ ... @@` header, or a description of a change without hunks.
+    pub(crate) header: String,
+    /// The hunk's lines in patch form.
+    pub(crate) patch: String,
+    old_start: u32,
+    new_start: u32,
+}
+
+/// Lists the staged changes hunk by hunk.
+pub(crate) fn staged_hunks() -> Result<Vec<Hunk>> {
+    let repo = Repository::open(".").context("Failed to open local git repository")?;
+    let diff = exact_staged_diff(&repo)?;
+    collect_hunks(&diff)
+}
+
+/// Lists the changes to tracked files that are not staged yet, hunk by hunk.
+pub(crate) fn unstaged_hunks() -> Result<Vec<Hunk>> {
+    let repo = Repository::open(".").context("Failed to open local git repository")?;
+    let diff = unstaged_diff(&repo)?;
+    collect_hunks(&diff)
+}
+
+/// Stages the unstaged hunks with the given ids, as `git add -p` does.
+///
+/// The ids refer to [`unstaged_hunks`]; the diff is recomputed, so they must
+/// come from a listing taken since the last change to the work tree or index.
+pub(crate) fn stage_hunks(ids: &[usize]) -> Result<()> {
+    let repo = Repository::open(".").context("Failed to open local git repository")?;
+    stage_unstaged_hunks(&repo, ids)
+}
+
+fn stage_unstaged_hunks(repo: &Repository, ids: &[usize]) -> Result<()> {
+    let diff = unstaged_diff(repo)?;
+    let hunks = collect_hunks(&diff)?;
+    let included: Vec<&Hunk> = hunks.iter().filter(|hunk| ids.contains(&hunk.id)).collect();
+    if included.is_empty() {
+        return Ok(());
+    }
+    with_hunk_filter(&included, |options| {
+        repo.apply(&diff, git2::ApplyLocation::Index, Some(options))
+            .context("Failed to stage the selected hunks")
+    })
+}
+
+/// Creates one commit per group of staged hunks, in order, on top of HEAD.
+///
+/// Each commit's tree is HEAD's tree with the hunks of its own and all earlier
+/// groups applied, so the commits build on one another. The index is left as
+/// it is: hunks no group claimed stay staged on top of the last commit.
+/// Hooks run as in [`commit_action`], with `pre-commit` checking the full
+/// staged state once up front.
+pub(crate) fn commit_hunk_groups(groups: &[(String, Vec<usize>)], verify: bool) -> Result<()> {
+    let repo = Repository::open(".").context("Failed to open local git repository")?;
+    commit_hunk_groups_in(&repo, groups, verify)
+}
+
+fn commit_hunk_groups_in(
+    repo: &Repository,
+    groups: &[(String, Vec<usize>)],
+    verify: bool,
+) -> Result<()> {
+    let signature = repo
+        .signature()
+        .context("Failed to determine git signature")?;
+
+    if verify {
+        run_hook(repo, "pre-commit", &[])?;
+    }
+
+    let diff = exact_staged_diff(repo)?;
+    let hunks = collect_hunks(&diff)?;
+    let base_tree = get_head_tree(repo)?;
+    let mut parents = get_parent_commits(repo)?;
+    let mut included = Vec::new();
+    let signer = CommitSigner::from_config(repo)?;
+
+    for (message, ids) in groups {
+        included.extend(hunks.iter().filter(|hunk| ids.contains(&hunk.id)));
+        let tree = apply_hunks(repo, &base_tree, &diff, &included)?;
+
+        let message = if verify {
+            filter_through_commit_msg(repo, message)?
+        } else {
+            message.clone()
+        };
+        let parent_references: Vec<&Commit<'_>> = parents.iter().collect();
+        let oid = write_commit(
+            repo,
+            signer.as_ref(),
+            &signature,
+            &signature,
+            &message,
+            &tree,
+            &parent_references,
+        )?;
+        move_head(
+            repo,
+            oid,
+            &format!("commit: {}", message.lines().next().unwrap_or_default()),
+        )?;
+        println!(
+            "{} {}",
+            oid.to_string()[..7].bright_green(),
+            message.lines().next().unwrap_or_default()
+        );
+
+        if let Err(e) = run_hook(repo, "post-commit", &[]) {
+            eprintln!("{} {e}", "warning:".bright_yellow().bold());
+        }
+        parents = vec![repo.find_commit(oid)?];
+    }
+
+    Ok(())
+}
+
+/// The staged diff without the whitespace leniency used for prompts, so its
+/// hunks can be applied back onto HEAD. Binary contents are included, since a
+/// binary file can only be applied as a whole.
+fn exact_staged_diff(repo: &Repository) -> Result<git2::Diff<'_>> {
+    let base_tree = get_head_tree(repo)?;
+    let index_tree = get_index_tree(repo)?;
+    let mut options = DiffOptions::new();
+    options.show_binary(true);
+    repo.diff_tree_to_tree(Some(&base_tree), Some(&index_tree), Some(&mut options))
+        .context("Failed to calculate difference between HEAD and index")
+}
+
+/// The changes between the index and the work tree, for tracked files only,
+/// with binary contents so binary files can be staged whole.
+fn unstaged_diff(repo: &Repository) -> Result<git2::Diff<'_>> {
+    let index = repo.index().context("Failed to open repository index")?;
+    let mut options = DiffOptions::new();
+    options.show_binary(true);
+    repo.diff_index_to_workdir(Some(&index), Some(&mut options))
+        .context("Failed to calculate difference between index and work tree")
+}
+
+fn collect_hunks(diff: &git2::Diff) -> Result<Vec<Hunk>> {
+    let mut hunks = Vec::new();
+    for (delta_index, delta) in diff.deltas().enumerate() {
+        let path = delta
+            .new_file()
+            .path()
+            .or_else(|| delta.old_file().path())
+            .map(Path::to_path_buf)
+            .ok_or(Shelfor::InvalidUtf8Path)?;
+        let patch = git2::Patch::from_diff(diff, delta_index)?;
+
+        let hunk_count = patch.as_ref().map_or(0, |patch| patch.num_hunks());
+        if hunk_count == 0 {
+            let kind = if delta.flags().is_binary() {
+                "binary file"
+            } else {
+                "without textual changes"
+            };
+            hunks.push(Hunk {
+                id: hunks.len() + 1,
+                path,
+                header: format!("({:?} {kind})", delta.status()),
+                patch: String::new(),
+                old_start: 0,
+                new_start: 0,
+            });
+            continue;
+        }
+
+        let patch = patch.expect("hunks come from a patch");
+        for hunk_index in 0..hunk_count {
+            let (hunk, line_count) = patch.hunk(hunk_index)?;
+            let mut text = String::new();
+            for line_index in 0..line_count {
+                let line = patch.line_in_hunk(hunk_index, line_index)?;
+                if matches!(line.origin(), '+' | '-' | ' ') {
+                    text.push(line.origin());
+                }
+                text.push_str(&String::from_utf8_lossy(line.content()));
+            }
+            hunks.push(Hunk {
+                id: hunks.len() + 1,
+                path: path.clone(),
+                header: String::from_utf8_lossy(hunk.header())
+                    .trim_end()
+                    .to_string(),
+                patch: text,
+                old_start: hunk.old_start(),
+                new_start: hunk.new_start(),
+            });
+        }
+    }
+    Ok(hunks)
+}
+
+/// Applies the `included` hunks of `diff` onto `base` and writes the result as a tree.
+fn apply_hunks<'repo>(
+    repo: &'repo Repository,
+    base: &Tree<'repo>,
+    diff: &git2::Diff,
+    included: &[&Hunk],
+) -> Result<Tree<'repo>> {
+    let mut index = with_hunk_filter(included, |options| {
+        repo.apply_to_tree(base, diff, Some(options))
+            .context("Failed to apply hunks onto HEAD")
+    })?;
+    let tree_id = index
+        .write_tree_to(repo)
+        .context("Failed to write partial tree")?;
+    repo.find_tree(tree_id)
+        .context("Failed to find partial tree")
+}
+
+/// Runs `apply` with options that only let the `included` hunks through.
+fn with_hunk_filter<T>(
+    included: &[&Hunk],
+    apply: impl FnOnce(&mut git2::ApplyOptions) -> Result<T>,
+) -> Result<T> {
+    let current_path = std::cell::RefCell::new(PathBuf::new());
+    let mut options = git2::ApplyOptions::new();
+    options.delta_callback(|delta| {
+        let Some(path) = delta.and_then(|delta| {
+            delta
+                .new_file()
+                .path()
+                .or_else(|| delta.old_file().path())
+                .map(Path::to_path_buf)
+        }) else {
+            return false;
+        };
+        let wanted = included.iter().any(|hunk| hunk.path == path);
+        *current_path.borrow_mut() = path;
+        wanted
+    });
+    options.hunk_callback(|hunk| {
+        hunk.is_some_and(|hunk| {
+            let path = current_path.borrow();
+            included.iter().any(|included| {
+                included.path == *path
+                    && included.old_start == hunk.old_start()
+                    && included.new_start == hunk.new_start()
+            })
+        })
+    });
+
+    apply(&mut options)
+}
+
+/// Retrieves recent commit history up to a specified depth.
+///
+/// Commits whose changed paths are all matched by `is_excluded` are left out,
+/// so housekeeping commits (lockfile bumps, generated code) don't shape the
+/// context.
+pub fn commit_history(
+    depth: &usize,
+    is_excluded: impl Fn(&Path) -> bool,
+) -> Result<Vec<(Oid, String)>> {
+    let repository = Repository::open(Path::new(".")).context("Failed to open Git repository")?;
+    let head = repository.head().context("Failed to get repository HEAD")?;
+    let head_commit = head
+        .peel_to_commit()
+        .context("Failed to peel HEAD to commit")?;
+
+    let mut revwalk = repository
+        .revwalk()
+        .context("Failed to create revision walker")?;
+    revwalk
+        .push(head_commit.id())
+        .context("Failed to push HEAD to revwalk")?;
+    revwalk
+        .set_sorting(git2::Sort::TIME)
+        .context("Failed to set revwalk sorting")?;
+
+    let history = revwalk
+        .take(*depth)
+        .filter_map(Result::ok) // Ignore revwalk errors
+        .filter_map(|oid| repository.find_commit(oid).ok()) // Ignore errors finding commits
+        .filter(|commit| !should_exclude_commit(&repository, commit, &is_excluded))
+        .map(|commit| {
+            (
+                commit.id(),
+                commit.message().unwrap_or_default().to_string(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    Ok(history)
+}
+
+/// The commit HEAD points at; `None` on an unborn branch.
+pub(crate) fn head_id() -> Result<Option<Oid>> {
+    let repo = Repository::open(".").context("Failed to open local git repository")?;
+    match repo.head() {
+        Ok(head) => Ok(Some(head.peel_to_commit()?.id())),
+        Err(e) if e.code() == git2::ErrorCode::UnbornBranch => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// How many commits `head` is ahead of `ancestor`, or `None` when `ancestor`
+/// is no longer in its history (or no longer exists), as after a rebase.
+pub(crate) fn commits_ahead(ancestor: Oid, head: Oid) -> Result<Option<usize>> {
+    let repo = Repository::open(".").context("Failed to open local git repository")?;
+    if repo.find_commit(ancestor).is_err() {
+        return Ok(None);
+    }
+    let (ahead, behind) = repo.graph_ahead_behind(head, ancestor)?;
+    Ok((behind == 0).then_some(ahead))
+}
+
+/// Messages of up to `limit` non-merge commits reachable from HEAD, newest
+/// first, with the id of HEAD itself; empty on an unborn branch.
+pub(crate) fn history_messages(limit: usize) -> Result<(Option<Oid>, Vec<String>)> {
+    let repo = Repository::open(".").context("Failed to open local git repository")?;
+    let head = match repo.head() {
+        Ok(head) => head.peel_to_commit()?.id(),
+        Err(e) if e.code() == git2::ErrorCode::UnbornBranch => return Ok((None, Vec::new())),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(git2::Sort::TIME)?;
+    revwalk.push(head)?;
+    let messages = revwalk
+        .filter_map(Result::ok)
+        .filter_map(|oid| repo.find_commit(oid).ok())
+        .filter(|commit| commit.parent_count() <= 1)
+        .take(limit)
+        .map(|commit| commit.message().unwrap_or_default().to_string())
+        .collect();
+    Ok((Some(head), messages))
+}
+
+/// Message files that may hold the start of the next commit message, most
+/// specific first: the merge (or cherry-pick) message, the squash message,
+/// the message of an unfinished commit attempt, and the commit template,
+/// which is `commit.template` or a `.gitmessage` at the top of the work tree.
+///
+/// Files are returned as they are, comments included; missing ones are skipped.
+pub(crate) fn pending_message_files() -> Result<Vec<(PathBuf, String)>> {
+    let repo = Repository::open(".").context("Failed to open local git repository")?;
+    pending_message_files_in(&repo)
+}
+
+fn pending_message_files_in(repo: &Repository) -> Result<Vec<(PathBuf, String)>> {
+    let template = repo
+        .config()
+        .context("Failed to read git config")?
+        .get_path("commit.template")
+        .ok()
+        .or_else(|| repo.workdir().map(|workdir| workdir.join(".gitmessage")));
+
+    let edit_message = repo.path().join("COMMIT_EDITMSG");
+    let candidates = [
+        Some(repo.path().join("MERGE_MSG")),
+        Some(repo.path().join("SQUASH_MSG")),
+        written_since_head_moved(repo, &edit_message)?.then_some(edit_message),
+        template,
+    ];
+    Ok(candidates
+        .into_iter()
+        .flatten()
+        .filter_map(|path| {
+            let content = fs::read_to_string(&path).ok()?;
+            Some((path, content))
+        })
+        .collect())
+}
+
+/// Whether `path` was written after HEAD last moved. COMMIT_EDITMSG outlives
+/// every commit, so it only holds an unfinished message when no commit, pull
+/// or checkout has happened since; without a reflog that can't be told.
+fn written_since_head_moved(repo: &Repository, path: &Path) -> Result<bool> {
+    let Ok(modified) = fs::metadata(path).and_then(|metadata| metadata.modified()) else {
+        return Ok(false);
+    };
+    let modified = modified
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs() as i64);
+    match repo.head() {
+        Err(e) if e.code() == git2::ErrorCode::UnbornBranch => return Ok(true),
+        Err(e) => return Err(e.into()),
+        Ok(_) => {}
+    }
+    let reflog = repo.reflog("HEAD")?;
+    // Reflog times have second precision, and a commit writes the file within
+    // the second HEAD moves, so only later seconds count.
+    Ok(reflog
+        .get(0)
+        .is_some_and(|entry| modified > entry.committer().when().seconds()))
+}
+
+/// The top of the work tree, or the git directory of a bare repository.
+pub(crate) fn work_tree_root() -> Result<PathBuf> {
+    let repo = Repository::open(".").context("Failed to open local git repository")?;
+    Ok(repo.workdir().unwrap_or_else(|| repo.path()).to_path_buf())
+}
+
+/// The repository's git directory, which identifies it across runs.
+pub(crate) fn repository_dir() -> Result<PathBuf> {
+    let repo = Repository::open(".").context("Failed to open local git repository")?;
+    Ok(repo
+        .path()
+        .canonicalize()
+        .unwrap_or_else(|_| repo.path().to_path_buf()))
+}
+
+/// Determines if a commit only changed paths matched by `is_excluded`.
+fn should_exclude_commit(
+    repository: &Repository,
+    commit: &Commit,
+    is_excluded: &impl Fn(&Path) -> bool,
+) -> bool {
+    let Ok(paths) = changed_paths(repository, commit) else {
+        return false; // If we can't diff the commit, don't exclude.
+    };
+    !paths.is_empty() && paths.iter().all(|path| is_excluded(path))
+}
+
+/// Lists the paths a commit changed relative to its first parent.
+fn changed_paths(repository: &Repository, commit: &Commit) -> Result<Vec<PathBuf>> {
+    let tree = commit.tree()?;
+    let parent_tree = match commit.parent(0) {
+        Ok(parent) => Some(parent.tree()?),
+        Err(_) => None,
+    };
+    let diff = repository.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)?;
+    Ok(diff
+        .deltas()
+        .filter_map(|delta| delta.new_file().path().or_else(|| delta.old_file().path()))
+        .map(Path::to_path_buf)
+        .collect())
+}
+
+/// Returns the name of the branch HEAD points to, or `None` when HEAD is detached.
+pub(crate) fn current_branch() -> Result<Option<String>> {
+    let repo = Repository::open(".").context("Failed to open local git repository")?;
+    // Read HEAD itself so a branch without commits yet still has a name.
+    let head = repo.find_reference("HEAD").context("Failed to read HEAD")?;
+    Ok(head
+        .symbolic_target()
+        .and_then(|target| target.strip_prefix("refs/heads/"))
+        .map(str::to_string))
+}
+
+/// Resolves the directory git runs hooks from, honouring `core.hooksPath`.
+///
+/// Relative `core.hooksPath` values are resolved against the top of the work
+/// tree, as git itself does.
+pub(crate) fn hooks_dir(repo: &Repository) -> Result<PathBuf> {
+    let config = repo.config().context("Failed to read git config")?;
+    match config.get_path("core.hooksPath") {
+        Ok(path) if path.is_absolute() => Ok(path),
+        Ok(path) => Ok(repo.workdir().unwrap_or_else(|| repo.path()).join(path)),
+        Err(_) => Ok(repo.path().join("hooks")),
+    }
+}
+
+/// Verifies that the `git` command is available in the system's PATH.
+pub(crate) fn verify_git_installation() -> Result<(), Shelfor> {
+    which::which("git").map_err(|_| Shelfor::GitNotInstalled)?;
+    Ok(())
+}
+
+/// Collects all staged changes and returns them as a formatted diff string.
+pub(crate) fn collect_changes() -> Result<String> {
+    let repository = Repository::open(Path::new(".")).context("Failed to open Git repository")?;
+    let diff = calculate_diff(&repository)?;
+    format_diff(&diff)
+}
+
+/// The staged changes to a single file.
+#[derive(Debug, Clone)]
+pub(crate) struct FileChange {
+    pub(crate) path: PathBuf,
+    pub(crate) insertions: usize,
+    pub(crate) deletions: usize,
+    /// The file's part of the patch, headers included.
+    pub(crate) patch: String,
+    /// Binary files have no textual patch.
+    pub(crate) binary: bool,
+}
+
+/// The changes a commit message describes.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum ChangeSource {
+    /// The staged changes on top of HEAD.
+    Staged,
+    /// HEAD's own changes together with the staged ones, for `--amend`.
+    Amend,
+    /// The combined changes of the commits in a range.
+    Range(CommitRange),
+}
+
+/// Collects the changes from `source` file by file, formatted as in [`collect_changes`].
+pub(crate) fn collect_file_changes(source: ChangeSource) -> Result<Vec<FileChange>> {
+    let repository = Repository::open(Path::new(".")).context("Failed to open Git repository")?;
+    let diff = match source {
+        ChangeSource::Staged => calculate_diff(&repository)?,
+        ChangeSource::Amend => {
+            let head = repository
+                .head()
+                .and_then(|head| head.peel_to_commit())
+                .context("There is no commit to amend")?;
+            let base = head.parent(0).and_then(|parent| parent.tree()).ok();
+            let index_tree = get_index_tree(&repository)?;
+            diff_trees(&repository, base.as_ref(), &index_tree)?
+        }
+        ChangeSource::Range(range) => {
+            let base = range
+                .base
+                .map(|base| {
+                    repository
+                        .find_commit(base)
+                        .and_then(|commit| commit.tree())
+                })
+                .transpose()?;
+            let tip = repository.find_commit(range.tip)?.tree()?;
+            diff_trees(&repository, base.as_ref(), &tip)?
+        }
+    };
+
+    let mut changes = Vec::new();
+    for (index, delta) in diff.deltas().enumerate() {
+        let path = delta
+            .new_file()
+            .path()
+            .or_else(|| delta.old_file().path())
+            .map(Path::to_path_buf)
+            .ok_or(Shelfor::InvalidUtf8Path)?;
+        let Some(mut patch) = git2::Patch::from_diff(&diff, index)? else {
+            // Binary files have no textual patch.
+            changes.push(FileChange {
+                path,
+                insertions: 0,
+                deletions: 0,
+                patch: String::new(),
+                binary: true,
+            });
+            continue;
+        };
+        let (_, insertions, deletions) = patch.line_stats()?;
+        let patch = String::from_utf8_lossy(&patch.to_buf()?).into_owned();
+        changes.push(FileChange {
+            path,
+            insertions,
+            deletions,
+            patch,
+            binary: false,
+        });
+    }
+    Ok(changes)
+}
+
+/// Gets the tree for the current HEAD, or an empty tree if HEAD does not exist (e.g., initial commit).
+fn get_head_tree<'repo>(repo: &'repo Repository) -> Result<Tree<'repo>> {
+    match repo.head() {
+        Ok(head) => head.peel_to_tree().context("Failed to peel HEAD to tree"),
+        Err(e) if e.code() == git2::ErrorCode::UnbornBranch => {
+            // On an unborn branch, there's no HEAD. Use an empty tree for the diff base.
+            let builder = repo.treebuilder(None)?;
+            let tree_id = builder.write()?;
+            repo.find_tree(tree_id)
+                .context("Failed to create an empty tree for unborn branch")
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Gets the tree for the repository's current index (staged files).
+fn get_index_tree<'repo>(repo: &'repo Repository) -> Result<Tree<'repo>> {
+    let mut index = repo.index().context("Failed to open repository index")?;
+    let tree_id = index
+        .write_tree()
+        .context("Failed to write index to tree")?;
+    repo.find_tree(tree_id).context("Failed to find index tree")
+}
+
+/// Calculates the diff between the HEAD tree and the index tree (staged changes).
+fn calculate_diff(repository: &Repository) -> Result<git2::Diff<'_>> {
+    let base_tree = get_head_tree(repository)?;
+    let index_tree = get_index_tree(repository)?;
+    diff_trees(repository, Some(&base_tree), &index_tree)
+        .context("Failed to calculate difference between HEAD and index")
+}
+
+/// Diffs two trees with the whitespace leniency used for prompts.
+fn diff_trees<'repo>(
+    repository: &'repo Repository,
+    old_tree: Option<&Tree<'repo>>,
+    new_tree: &Tree<'repo>,
+) -> Result<git2::Diff<'repo>> {
+    let mut diff_options = DiffOptions::new();
+    diff_options
+        .context_lines(4)
+        .ignore_whitespace_change(true)
+        .ignore_whitespace_eol(true);
+
+    repository
+        .diff_tree_to_tree(old_tree, Some(new_tree), Some(&mut diff_options))
+        .context("Failed to calculate difference")
+}
+
+/// Formats a `git2::Diff` into a standard patch string.
+fn format_diff(difference: &git2::Diff) -> Result<String> {
+    let mut formatted_difference = String::new();
+    difference
+        .print(git2::DiffFormat::Patch, |_delta, _hunk, line| {
+            // The line content from git2 does not include the prefix (+, -, ' ').
+            // We reconstruct the line here to form a standard patch format.
+            if let Ok(content) = std::str::from_utf8(line.content()) {
+                match line.origin() {
+                    '+' | '-' | ' ' => {
+                        formatted_difference.push(line.origin());
+                        formatted_difference.push_str(content);
+                    }
+                    // For headers ('F', 'H'), etc., the content is the full line.
+                    _ => {
+                        formatted_difference.push_str(content);
+                    }
+                }
+                true
+            } else {
+                false // Non-UTF8 content in diff, abort.
+            }
+        })
+        .context("Failed to format difference")?;
+
+    Ok(formatted_difference)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::tempdir;
+
+    fn write_hook(repo: &Repository, name: &str, script: &str) -> Result<()> {
+        let path = hooks_dir(repo)?.join(name);
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(&path, script)?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+        Ok(())
+    }
+
+    #[test]
+    fn commit_msg_hook_rewrites_and_failing_hooks_abort() -> Result<()> {
+        let dir = tempdir()?;
+        let repo = Repository::init(dir.path())?;
+
+        assert_eq!(
+            filter_through_commit_msg(&repo, "feat: add x")?,
+            "feat: add x\n"
+        );
+
+        write_hook(
+            &repo,
+            "commit-msg",
+            "#!/bin/sh\nprintf '\\nSigned-off-by: Hook\\n' >> \"$1\"\n",
+        )?;
+        assert_eq!(
+            filter_through_commit_msg(&repo, "feat: add x")?,
+            "feat: add x\n\nSigned-off-by: Hook\n"
+        );
+
+        write_hook(&repo, "pre-commit", "#!/bin/sh\nexit 1\n")?;
+        let err = run_hook(&repo, "pre-commit", &[]).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Shelfor>(),
+            Some(Shelfor::HookFailed("pre-commit"))
+        ));
+        assert!(!run_hook(&repo, "post-commit", &[])?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn hunks_apply_independently_of_skipped_ones() -> Result<()> {
+        let dir = tempdir()?;
+        let repo = Repository::init(dir.path())?;
+        let signature = git2::Signature::now("test", "test@example.com")?;
+
+        let original: String = (1..=30).map(|n| format!("line {n}\n")).collect();
+        fs::write(dir.path().join("a.txt"), &original)?;
+        let mut index = repo.index()?;
+        index.add_path(Path::new("a.txt"))?;
+        let tree = repo.find_tree(index.write_tree()?)?;
+        repo.commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])?;
+
+        // Two hunks far apart; the first shifts every later line.
+        let changed = original
+            .replace("line 2\n", "line 2\nadded a\nadded b\n")
+            .replace("line 28\n", "line 28 changed\n");
+        fs::write(dir.path().join("a.txt"), &changed)?;
+        fs::write(dir.path().join("b.txt"), "new file\n")?;
+        index.add_path(Path::new("a.txt"))?;
+        index.add_path(Path::new("b.txt"))?;
+        index.write()?;
+
+        let diff = exact_staged_diff(&repo)?;
+        let hunks = collect_hunks(&diff)?;
+        assert_eq!(hunks.len(), 3);
+        assert_eq!(
+            hunks.iter().map(|hunk| hunk.id).collect::<Vec<_>>(),
+            [1, 2, 3]
+        );
+
+        let base = get_head_tree(&repo)?;
+        let partial = apply_hunks(&repo, &base, &diff, &[&hunks[1]])?;
+        let blob = partial.get_path(Path::new("a.txt"))?.to_object(&repo)?;
+        let content = std::str::from_utf8(blob.as_blob().unwrap().content())?.to_string();
+        assert_eq!(content, original.replace("line 28\n", "line 28 changed\n"));
+        assert!(partial.get_path(Path::new("b.txt")).is_err());
+
+        let all: Vec<&Hunk> = hunks.iter().collect();
+        let full = apply_hunks(&repo, &base, &diff, &all)?;
+        assert_eq!(full.id(), get_index_tree(&repo)?.id());
+
+        Ok(())
+    }
+
+    #[test]
+    fn merge_commits_get_both_parents_and_clear_the_merge_state() -> Result<()> {
+        let dir = tempdir()?;
+        let repo = Repository::init(dir.path())?;
+        let signature = git2::Signature::now("test", "test@example.com")?;
+        let tree = repo.find_tree(repo.index()?.write_tree()?)?;
+        let base = repo.commit(Some("HEAD"), &signature, &signature, "base", &tree, &[])?;
+        let base = repo.find_commit(base)?;
+        let theirs = repo.commit(None, &signature, &signature, "theirs", &tree, &[&base])?;
+        assert_eq!(pending_operation_in(&repo)?, None);
+
+        fs::write(repo.path().join("MERGE_HEAD"), format!("{theirs}\n"))?;
+        fs::write(repo.path().join("MERGE_MSG"), "Merge branch 'theirs'\n")?;
+        let operation = pending_operation_in(&repo)?;
+        assert_eq!(operation, Some(PendingOperation::Merge));
+        let parents: Vec<Oid> = operation_parents(&repo, operation)?
+            .iter()
+            .map(Commit::id)
+            .collect();
+        assert_eq!(parents, vec![base.id(), theirs]);
+
+        finish_operation(&repo, operation)?;
+        assert_eq!(pending_operation_in(&repo)?, None);
+        assert!(!repo.path().join("MERGE_MSG").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn post_commit_runs_without_verification() -> Result<()> {
+        let dir = tempdir()?;
+        let repo = Repository::init(dir.path())?;
+        let mut config = repo.config()?;
+        config.set_str("user.name", "test")?;
+        config.set_str("user.email", "test@example.com")?;
+        write_hook(
+            &repo,
+            "post-commit",
+            "#!/bin/sh\necho ran >> .git/post-commit.log\n",
+        )?;
+        write_hook(&repo, "pre-commit", "#!/bin/sh\nexit 1\n")?;
+
+        let mut index = repo.index()?;
+        fs::write(dir.path().join("file.txt"), "one\n")?;
+        index.add_path(Path::new("file.txt"))?;
+        index.write()?;
+        commit_index_in(&repo, "first".to_string(), false, false)?;
+
+        fs::write(dir.path().join("file.txt"), "two\n")?;
+        index.add_path(Path::new("file.txt"))?;
+        index.write()?;
+        let ids = collect_hunks(&exact_staged_diff(&repo)?)?
+            .iter()
+            .map(|hunk| hunk.id)
+            .collect();
+        commit_hunk_groups_in(&repo, &[("second".to_string(), ids)], false)?;
+
+        let log = fs::read_to_string(repo.path().join("post-commit.log"))?;
+        assert_eq!(log, "ran\nran\n");
+        Ok(())
+    }
+
+    #[test]
+    fn only_cherry_picks_keep_the_original_author() -> Result<()> {
+        let dir = tempdir()?;
+        let repo = Repository::init(dir.path())?;
+        let committer = git2::Signature::now("committer", "committer@example.com")?;
+        let author = git2::Signature::now("author", "author@example.com")?;
+        let tree = repo.find_tree(repo.index()?.write_tree()?)?;
+        let picked = repo.commit(None, &author, &author, "picked", &tree, &[])?;
+        let author_of = |operation| -> Result<Option<String>> {
+            Ok(operation_author(&repo, operation, &committer)?
+                .name()
+                .map(str::to_string))
+        };
+
+        assert_eq!(author_of(None)?.as_deref(), Some("committer"));
+        fs::write(repo.path().join("CHERRY_PICK_HEAD"), format!("{picked}\n"))?;
+        let operation = Some(PendingOperation::CherryPick { sequence: false });
+        assert_eq!(author_of(operation)?.as_deref(), Some("author"));
+
+        // Commits made while a rebase is stopped are the committer's.
+        fs::write(repo.path().join("REBASE_HEAD"), format!("{picked}\n"))?;
+        let rebase = Some(PendingOperation::Rebase);
+        assert_eq!(author_of(rebase)?.as_deref(), Some("committer"));
+        Ok(())
+    }
+
+    #[test]
+    fn commit_editmsg_is_pending_only_until_head_moves() -> Result<()> {
+        let dir = tempdir()?;
+        let repo = Repository::init(dir.path())?;
+        let signature = git2::Signature::now("test", "test@example.com")?;
+        let tree = repo.find_tree(repo.index()?.write_tree()?)?;
+        let edit_message = repo.path().join("COMMIT_EDITMSG");
+        let pending = |repo: &Repository| -> Result<Vec<PathBuf>> {
+            Ok(pending_message_files_in(repo)?
+                .into_iter()
+                .map(|(path, _)| path)
+                .collect())
+        };
+
+        // An aborted first commit.
+        fs::write(&edit_message, "feat: half-written\n")?;
+        assert_eq!(pending(&repo)?, vec![edit_message.clone()]);
+
+        // HEAD then moves, as a pull would, after the file was written.
+        let written = std::time::SystemTime::now() - std::time::Duration::from_secs(60);
+        fs::File::options()
+            .write(true)
+            .open(&edit_message)?
+            .set_modified(written)?;
+        repo.commit(Some("HEAD"), &signature, &signature, "pulled", &tree, &[])?;
+        assert!(pending(&repo)?.is_empty());
+
+        // A later attempt that was abandoned.
+        let written = std::time::SystemTime::now() + std::time::Duration::from_secs(60);
+        fs::File::options()
+            .write(true)
+            .open(&edit_message)?
+            .set_modified(written)?;
+        assert_eq!(pending(&repo)?, vec![edit_message]);
+        Ok(())
+    }
+
+    #[test]
+    fn split_commits_carry_binary_files_whole() -> Result<()> {
+        let dir = tempdir()?;
+        let repo = Repository::init(dir.path())?;
+        let mut config = repo.config()?;
+        config.set_str("user.name", "test")?;
+        config.set_str("user.email", "test@example.com")?;
+
+        let mut index = repo.index()?;
+        fs::write(dir.path().join("logo.png"), b"\x89PNG\0old")?;
+        index.add_path(Path::new("logo.png"))?;
+        index.write()?;
+        commit_index_in(&repo, "init".to_string(), false, false)?;
+
+        fs::write(dir.path().join("logo.png"), b"\x89PNG\0new")?;
+        index.add_path(Path::new("logo.png"))?;
+        index.write()?;
+        let hunks = collect_hunks(&exact_staged_diff(&repo)?)?;
+        assert_eq!(hunks.len(), 1);
+        assert_eq!(hunks[0].header, "(Modified binary file)");
+        commit_hunk_groups_in(
+            &repo,
+            &[("update logo".to_string(), vec![hunks[0].id])],
+            false,
+        )?;
+
+        let tree = repo.head()?.peel_to_tree()?;
+        let blob = repo.find_blob(tree.get_path(Path::new("logo.png"))?.id())?;
+        assert_eq!(blob.content(), b"\x89PNG\0new");
+        Ok(())
+    }
+
+    #[test]
+    fn staging_selected_hunks_leaves_the_rest_unstaged() -> Result<()> {
+        let dir = tempdir()?;
+        let repo = Repository::init(dir.path())?;
+        let signature = git2::Signature::now("test", "test@example.com")?;
+
+        let original: String = (1..=30).map(|n| format!("line {n}\n")).collect();
+        fs::write(dir.path().join("a.txt"), &original)?;
+        let mut index = repo.index()?;
+        index.add_path(Path::new("a.txt"))?;
+        index.write()?;
+        let tree = repo.find_tree(index.write_tree()?)?;
+        repo.commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])?;
+
+        let changed = original
+            .replace("line 2\n", "line 2\nadded\n")
+            .replace("line 28\n", "line 28 changed\n");
+        fs::write(dir.path().join("a.txt"), &changed)?;
+
+        let hunks = collect_hunks(&unstaged_diff(&repo)?)?;
+        assert_eq!(hunks.len(), 2);
+        stage_unstaged_hunks(&repo, &[2])?;
+
+        let staged = collect_hunks(&exact_staged_diff(&repo)?)?;
+        assert_eq!(staged.len(), 1);
+        assert!(staged[0].patch.contains("+line 28 changed"));
+
+        let remaining = collect_hunks(&unstaged_diff(&repo)?)?;
+        assert_eq!(remaining.len(), 1);
+        assert!(remaining[0].patch.contains("+added"));
+
+        // Binary files are offered, and staged, as a whole.
+        fs::write(dir.path().join("logo.png"), b"\x89PNG\0")?;
+        index.read(true)?;
+        index.add_path(Path::new("logo.png"))?;
+        index.write()?;
+        fs::write(dir.path().join("logo.png"), b"\x89PNG\0new")?;
+        let binary = collect_hunks(&unstaged_diff(&repo)?)?
+            .into_iter()
+            .find(|hunk| hunk.path == Path::new("logo.png"))
+            .expect("the binary change is listed");
+        assert_eq!(binary.header, "(Modified binary file)");
+        stage_unstaged_hunks(&repo, &[binary.id])?;
+        index.read(true)?;
+        let entry = index.get_path(Path::new("logo.png"), 0).expect("staged");
+        assert_eq!(repo.find_blob(entry.id)?.content(), b"\x89PNG\0new");
+
+        Ok(())
+    }
+
+    #[test]
+    fn squashing_a_range_keeps_later_commits_and_trees() -> Result<()> {
+        let dir = tempdir()?;
+        let repo = Repository::init(dir.path())?;
+        let mut config = repo.config()?;
+        config.set_str("user.name", "test")?;
+        config.set_str("user.email", "test@example.com")?;
+        let signature = repo.signature()?;
+
+        let commit = |name: &str| -> Result<Oid> {
+            fs::write(dir.path().join(name), name)?;
+            let mut index = repo.index()?;
+            index.add_path(Path::new(name))?;
+            let tree = repo.find_tree(index.write_tree()?)?;
+            let parents = get_parent_commits(&repo)?;
+            let parents: Vec<&Commit<'_>> = parents.iter().collect();
+            Ok(repo.commit(Some("HEAD"), &signature, &signature, name, &tree, &parents)?)
+        };
+        let root = commit("a")?;
+        commit("b")?;
+        let c = commit("c")?;
+        let head = commit("d")?;
+        let head_tree = repo.find_commit(head)?.tree_id();
+
+        let range = CommitRange {
+            base: Some(root),
+            tip: c,
+        };
+        let (squashed, later) = split_first_parent_history(&repo, range)?;
+        assert_eq!(squashed.len(), 2);
+        assert_eq!(later.len(), 1);
+
+        let new_head = replace_range(&repo, range, "b and c")?;
+        let new_head = repo.find_commit(new_head)?;
+        assert_eq!(new_head.tree_id(), head_tree);
+        assert_eq!(new_head.message(), Some("d"));
+        let combined = new_head.parent(0)?;
+        assert_eq!(combined.message(), Some("b and c"));
+        assert_eq!(combined.parent_id(0)?, root);
+        assert_eq!(repo.head()?.peel_to_commit()?.id(), new_head.id());
+
+        let unrelated = CommitRange {
+            base: Some(head),
+            tip: c,
+        };
+        assert!(split_first_parent_history(&repo, unrelated).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,255 @@
+use anyhow::{Context, Result, anyhow};
+use git2::{Repository, Signature};
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use tempfile::NamedTempFile;
+
+/// Signature formats selected by `gpg.format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SignatureFormat {
+    OpenPgp,
+    X509,
+    Ssh,
+}
+
+impl SignatureFormat {
+    fn parse(value: &str) -> Result<Self> {
+        match value {
+            "openpgp" => Ok(Self::OpenPgp),
+            "x509" => Ok(Self::X509),
+            "ssh" => Ok(Self::Ssh),
+            other => Err(anyhow!("Unsupported gpg.format {other:?}")),
+        }
+    }
+
+    fn config_name(self) -> &'static str {
+        match self {
+            Self::OpenPgp => "openpgp",
+            Self::X509 => "x509",
+            Self::Ssh => "ssh",
+        }
+    }
+
+    fn default_program(self) -> &'static str {
+        match self {
+            Self::OpenPgp => "gpg",
+            Self::X509 => "gpgsm",
+            Self::Ssh => "ssh-keygen",
+        }
+    }
+}
+
+/// Signs commit buffers the way `git commit -S` does, following `commit.gpgsign`,
+/// `gpg.format`, `gpg.<format>.program` and `user.signingkey`.
+#[derive(Debug)]
+pub(crate) struct CommitSigner {
+    format: SignatureFormat,
+    program: String,
+    key: Option<String>,
+}
+
+impl CommitSigner {
+    /// Reads the signing setup from the repository's config, or `None` when
+    /// `commit.gpgsign` is off.
+    pub(crate) fn from_config(repo: &Repository) -> Result<Option<Self>> {
+        let config = repo.config().context("Failed to read git config")?;
+        if !config.get_bool("commit.gpgsign").unwrap_or(false) {
+            return Ok(None);
+        }
+
+        let format = match config.get_string("gpg.format") {
+            Ok(value) => SignatureFormat::parse(&value)?,
+            Err(_) => SignatureFormat::OpenPgp,
+        };
+        let program = config
+            .get_string(&format!("gpg.{}.program", format.config_name()))
+            .or_else(|e| match format {
+                // `gpg.program` predates the per-format settings and only applies to OpenPGP.
+                SignatureFormat::OpenPgp => config.get_string("gpg.program"),
+                _ => Err(e),
+            })
+            .unwrap_or_else(|_| format.default_program().to_string());
+        let key = config
+            .get_string("user.signingkey")
+            .ok()
+            .filter(|key| !key.is_empty());
+
+        if format == SignatureFormat::Ssh && key.is_none() {
+            return Err(anyhow!(
+                "gpg.format is ssh but user.signingkey is not set, so the commit cannot be signed"
+            ));
+        }
+        Ok(Some(Self {
+            format,
+            program,
+            key,
+        }))
+    }
+
+    /// Signs `payload`, a commit buffer, returning the armored signature.
+    pub(crate) fn sign(&self, payload: &str, committer: &Signature) -> Result<String> {
+        let signature = match self.format {
+            SignatureFormat::OpenPgp | SignatureFormat::X509 => {
+                // Without a configured key, gpg picks one matching the committer.
+                let key = self.key.clone().unwrap_or_else(|| {
+                    format!(
+                        "{} <{}>",
+                        committer.name().unwrap_or_default(),
+                        committer.email().unwrap_or_default()
+                    )
+                });
+                self.sign_with_gpg(payload, &key)?
+            }
+            SignatureFormat::Ssh => self.sign_with_ssh(payload)?,
+        };
+
+        if signature.trim().is_empty() {
+            return Err(anyhow!("{} returned an empty signature", self.program));
+        }
+        Ok(signature)
+    }
+
+    fn sign_with_gpg(&self, payload: &str, key: &str) -> Result<String> {
+        let mut child = Command::new(&self.program)
+            .args(["--status-fd=2", "-bsau", key])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Failed to run {}", self.program))?;
+        child
+            .stdin
+            .take()
+            .expect("stdin is piped")
+            .write_all(payload.as_bytes())?;
+        let output = child.wait_with_output()?;
+
+        if !output.status.success() {
+            return Err(anyhow!(
+                "{} failed to sign the commit: {}",
+                self.program,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        String::from_utf8(output.stdout).context("Signature is not valid UTF-8")
+    }
+
+    fn sign_with_ssh(&self, payload: &str) -> Result<String> {
+        let key = self.key.as_deref().expect("checked in from_config");
+
+        // A literal public key is handed over in a file and the private half
+        // is looked up in the agent, as git does.
+        let literal = key
+            .strip_prefix("key::")
+            .or_else(|| key.starts_with("ssh-").then_some(key));
+        let mut key_file = None;
+        let key_path = match literal {
+            Some(public_key) => {
+                let mut file = NamedTempFile::new()?;
+                writeln!(file, "{public_key}")?;
+                key_file.insert(file).path().to_path_buf()
+            }
+            None => key.into(),
+        };
+
+        let mut buffer = NamedTempFile::new()?;
+        buffer.write_all(payload.as_bytes())?;
+        let mut command = Command::new(&self.program);
+        command
+            .args(["-Y", "sign", "-n", "git", "-f"])
+            .arg(&key_path);
+        if literal.is_some() {
+            command.arg("-U");
+        }
+        let output = command
+            .arg(buffer.path())
+            .stdin(Stdio::null())
+            .output()
+            .with_context(|| format!("Failed to run {}", self.program))?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "{} failed to sign the commit: {}",
+                self.program,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        // ssh-keygen writes the signature next to the signed file.
+        let mut signature_path = buffer.path().as_os_str().to_owned();
+        signature_path.push(".sig");
+        let signature_path = PathBuf::from(signature_path);
+        let signature = fs::read_to_string(&signature_path)
+            .with_context(|| format!("{} did not write a signature", self.program))?;
+        let _ = fs::remove_file(&signature_path);
+        Ok(signature)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::tempdir;
+
+    const STUB_SIGNATURE: &str =
+        "-----BEGIN PGP SIGNATURE-----\n\nc3R1Yg==\n-----END PGP SIGNATURE-----\n";
+
+    #[test]
+    fn commits_are_signed_through_the_configured_program() -> Result<()> {
+        let dir = tempdir()?;
+        let repo = Repository::init(dir.path().join("repo"))?;
+
+        // Records its arguments and the payload, then prints a fixed signature.
+        let stub = dir.path().join("stub-gpg");
+        fs::write(
+            &stub,
+            format!(
+                "#!/bin/sh\necho \"$@\" > \"{0}/args\"\ncat > \"{0}/payload\"\nprintf '%b' '{1}'\n",
+                dir.path().display(),
+                STUB_SIGNATURE.replace('\n', "\\n")
+            ),
+        )?;
+        fs::set_permissions(&stub, fs::Permissions::from_mode(0o755))?;
+
+        let mut config = repo.config()?;
+        config.set_str("user.name", "test")?;
+        config.set_str("user.email", "test@example.com")?;
+        assert!(CommitSigner::from_config(&repo)?.is_none());
+        config.set_bool("commit.gpgsign", true)?;
+        config.set_str("gpg.program", stub.to_str().unwrap())?;
+        config.set_str("user.signingkey", "ABCDEF")?;
+
+        let signer = CommitSigner::from_config(&repo)?;
+        let signature = repo.signature()?;
+        let tree = repo.find_tree(repo.index()?.write_tree()?)?;
+        let oid = super::super::write_commit(
+            &repo,
+            signer.as_ref(),
+            &signature,
+            &signature,
+            "signed",
+            &tree,
+            &[],
+        )?;
+
+        let (recorded_signature, signed_data) = repo.extract_signature(&oid, None)?;
+        assert_eq!(recorded_signature.as_str(), Some(STUB_SIGNATURE));
+        assert_eq!(
+            fs::read_to_string(dir.path().join("payload"))?,
+            signed_data.as_str().unwrap()
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("args"))?.trim(),
+            "--status-fd=2 -bsau ABCDEF"
+        );
+        assert_eq!(repo.find_commit(oid)?.message(), Some("signed"));
+
+        config.set_str("gpg.format", "ssh")?;
+        config.remove("user.signingkey")?;
+        assert!(CommitSigner::from_config(&repo).is_err());
+
+        Ok(())
+    }
+}
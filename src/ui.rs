@@ -4,6 +4,10 @@ pub(super) enum UserAction {
     RegenerateMessage,
     CommitChanges,
     EditWithEditor,
+    ToggleTrailers,
+    PickCandidate,
+    MergeCandidates,
+    StageChanges,
     Quit,
     Cancelled,
 }
@@ -11,23 +15,78 @@ pub(super) enum UserAction {
 const PROMPT: &str = "What would you like to do next?";
 const OPTION_REGENERATE: &str = "Regenerate message";
 const OPTION_EDIT: &str = "Edit with Editor";
+const OPTION_TRAILERS: &str = "Toggle trailers";
+const OPTION_PICK: &str = "Go back to another suggestion";
+const OPTION_MERGE: &str = "Merge two suggestions in the editor";
+const OPTION_STAGE: &str = "Stage more changes";
 const OPTION_COMMIT: &str = "Commit changes";
 const OPTION_QUIT: &str = "Quit";
 
-pub(super) fn user_selection() -> Result<UserAction> {
+/// Which situational actions the menu offers besides the standard ones.
+pub(super) struct MenuOptions {
+    pub(super) trailers: bool,
+    pub(super) history: bool,
+    pub(super) staging: bool,
+}
+
+pub(super) fn user_selection(menu: MenuOptions) -> Result<UserAction> {
     use dialoguer::{Select, theme::ColorfulTheme};
-    let options = vec![OPTION_REGENERATE, OPTION_EDIT, OPTION_COMMIT, OPTION_QUIT];
+    let mut options = vec![
+        (OPTION_REGENERATE, UserAction::RegenerateMessage),
+        (OPTION_EDIT, UserAction::EditWithEditor),
+    ];
+    if menu.history {
+        options.push((OPTION_PICK, UserAction::PickCandidate));
+        options.push((OPTION_MERGE, UserAction::MergeCandidates));
+    }
+    if menu.trailers {
+        options.push((OPTION_TRAILERS, UserAction::ToggleTrailers));
+    }
+    if menu.staging {
+        options.push((OPTION_STAGE, UserAction::StageChanges));
+    }
+    options.push((OPTION_COMMIT, UserAction::CommitChanges));
+    options.push((OPTION_QUIT, UserAction::Quit));
+
+    let labels: Vec<&str> = options.iter().map(|(label, _)| *label).collect();
     let selection = Select::with_theme(&ColorfulTheme::default())
         .with_prompt(PROMPT)
         .default(0)
-        .items(&options)
+        .items(&labels)
         .interact();
 
     match selection {
-        Ok(0) => Ok(UserAction::RegenerateMessage),
-        Ok(1) => Ok(UserAction::EditWithEditor),
-        Ok(2) => Ok(UserAction::CommitChanges),
-        Ok(3) => Ok(UserAction::Quit),
+        Ok(index) if index < options.len() => Ok(options.swap_remove(index).1),
         _ => Ok(UserAction::Cancelled),
     }
 }
+
+/// Lets the user tick items on or off; `None` when the prompt is cancelled.
+pub(super) fn select_enabled(
+    prompt: &str,
+    items: &[String],
+    enabled: &[bool],
+) -> Result<Option<Vec<bool>>> {
+    use dialoguer::{MultiSelect, theme::ColorfulTheme};
+    let selection = MultiSelect::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .items(items)
+        .defaults(enabled)
+        .interact_opt()?;
+
+    Ok(selection.map(|chosen| (0..items.len()).map(|i| chosen.contains(&i)).collect()))
+}
+
+/// Lets the user pick one item; `None` when the prompt is cancelled.
+pub(super) fn select_index(
+    prompt: &str,
+    items: &[String],
+    default: usize,
+) -> Result<Option<usize>> {
+    use dialoguer::{Select, theme::ColorfulTheme};
+    Ok(Select::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .items(items)
+        .default(default)
+        .interact_opt()?)
+}